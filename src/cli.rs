@@ -5,28 +5,75 @@ use std::{
 
 use clap::Parser;
 use clap_stdin::FileOrStdin;
+use indexmap::IndexMap;
+
+use crate::context::merge_values;
 
 type ValueMap = HashMap<String, serde_json::Value>;
 
+/// Environment variable holding frontmatter overrides, like `--overrides`.
+pub const OVERRIDES_ENV: &str = "WHISKERS_OVERRIDES";
+
+/// Environment variable holding color overrides, like `--color-overrides`.
+pub const COLOR_OVERRIDES_ENV: &str = "WHISKERS_COLOR_OVERRIDES";
+
+/// Prefix of environment variables setting a single frontmatter override, like
+/// `--set`. Nested keys are separated by a double underscore, so
+/// `WHISKERS_SET_a__b=c` is equivalent to `--set a.b=c`.
+pub const SET_ENV_PREFIX: &str = "WHISKERS_SET_";
+
 #[derive(Parser, Debug)]
 #[command(version, about)]
 #[allow(clippy::struct_excessive_bools)] // not a problem for cli flags
 pub struct Args {
     /// Path to the template file, or - for stdin
-    #[arg(required_unless_present_any = ["list_functions", "list_flavors", "list_accents"])]
+    #[arg(required_unless_present_any = ["list_functions", "list_flavors", "list_accents", "list_colors", "export", "audit_accents"])]
     pub template: Option<FileOrStdin>,
 
     /// Render a single flavor instead of all four
+    ///
+    /// Either a Catppuccin flavor (latte, frappe, macchiato, mocha) or a
+    /// flavor derived in the color overrides.
     #[arg(long, short)]
-    pub flavor: Option<Flavor>,
+    pub flavor: Option<String>,
 
     /// Set color overrides
+    ///
+    /// Accepts a JSON string, or a path to a JSON, YAML or TOML file. Can be
+    /// given multiple times, with later overrides deep-merged over earlier ones.
     #[arg(long, value_parser = json_map::<ColorOverrides>)]
-    pub color_overrides: Option<ColorOverrides>,
+    pub color_overrides: Vec<ColorOverrides>,
+
+    /// Use a custom palette instead of the Catppuccin palette
+    ///
+    /// Accepts a JSON string, or a path to a JSON, YAML or TOML file. The
+    /// palette must contain every Catppuccin flavor, each with every
+    /// Catppuccin color.
+    #[arg(long, value_parser = json_map::<CustomPalette>)]
+    pub palette: Option<CustomPalette>,
 
     /// Set frontmatter overrides
+    ///
+    /// Accepts a JSON string, or a path to a JSON, YAML or TOML file. Can be
+    /// given multiple times, with later overrides deep-merged over earlier ones.
     #[arg(long, value_parser = json_map::<ValueMap>)]
-    pub overrides: Option<ValueMap>,
+    pub overrides: Vec<ValueMap>,
+
+    /// Set a single frontmatter override, e.g. `--set path.to.key=value`
+    ///
+    /// The value is parsed as a boolean or a number if possible, and is
+    /// otherwise used as a string. Can be given multiple times, and takes
+    /// precedence over `--overrides`. `--set` and `--set-json` are applied in
+    /// the order they are given, so later values win.
+    #[arg(long = "set", value_name = "PATH=VALUE", value_parser = set_override)]
+    pub set: Vec<SetOverride>,
+
+    /// Like `--set`, but the value is parsed as JSON
+    ///
+    /// Takes precedence over `--overrides`, and is applied in command-line
+    /// order together with `--set`.
+    #[arg(long = "set-json", value_name = "PATH=JSON", value_parser = set_json_override)]
+    pub set_json: Vec<SetOverride>,
 
     /// Instead of creating an output, check it against an example
     ///
@@ -52,9 +99,48 @@ pub struct Args {
     #[arg(long)]
     pub list_accents: bool,
 
-    /// Output format of --list-functions
+    /// Export the palette as a swatch file instead of rendering a template
+    ///
+    /// The swatch file is written to stdout. Exports every flavor unless
+    /// `--flavor` is given, and respects `--color-overrides` and `--palette`.
+    #[arg(long, value_name = "FORMAT")]
+    pub export: Option<ExportFormat>,
+
+    /// List every color of the palette, with color overrides applied
+    ///
+    /// Lists every flavor unless `--flavor` is given.
+    #[arg(long)]
+    pub list_colors: bool,
+
+    /// Report accent pairs that are hard to tell apart with color blindness
+    ///
+    /// Every pair of accents is compared after simulating protanopia,
+    /// deuteranopia, tritanopia and achromatopsia, and pairs closer than
+    /// `--min-delta-e` are listed. Audits every flavor unless `--flavor` is
+    /// given.
+    #[arg(long)]
+    pub audit_accents: bool,
+
+    /// Minimum CIEDE2000 distance between accents for --audit-accents
+    #[arg(long, value_name = "DISTANCE", default_value_t = 5.0)]
+    pub min_delta_e: f64,
+
+    /// Output format of --list-functions, --list-flavors, --list-accents,
+    /// --list-colors and --audit-accents
     #[arg(short, long, default_value = "json")]
     pub output_format: OutputFormat,
+
+    /// Overrides read from the environment, see [`Args::with_env_overrides`]
+    #[arg(skip)]
+    pub env: EnvOverrides,
+}
+
+/// Overrides read from `WHISKERS_*` environment variables.
+#[derive(Clone, Debug, Default)]
+pub struct EnvOverrides {
+    pub overrides: Option<ValueMap>,
+    pub color_overrides: Option<ColorOverrides>,
+    pub set: Vec<SetOverride>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -65,6 +151,22 @@ enum Error {
     #[error("Invalid JSON file argument: {message}")]
     InvalidJsonFileArg { message: String },
 
+    #[error("Invalid YAML file argument: {message}")]
+    InvalidYamlFileArg { message: String },
+
+    #[error("Invalid TOML file argument: {message}")]
+    InvalidTomlFileArg { message: String },
+
+    #[error("Invalid --set argument {arg:?}: {message}")]
+    InvalidSetArg { arg: String, message: String },
+
+    #[error("Invalid environment variable {name}")]
+    InvalidEnvVar {
+        name: String,
+        #[source]
+        source: Box<Self>,
+    },
+
     #[error("Failed to read file: {path}")]
     ReadFile {
         path: String,
@@ -73,26 +175,177 @@ enum Error {
     },
 }
 
-#[derive(Copy, Clone, Debug, clap::ValueEnum)]
-pub enum Flavor {
-    Latte,
-    Frappe,
-    Macchiato,
-    Mocha,
+/// A single `path.to.key=value` override from `--set` or `--set-json`.
+#[derive(Clone, Debug)]
+pub struct SetOverride {
+    pub path: Vec<String>,
+    pub value: serde_json::Value,
+}
+
+impl SetOverride {
+    /// Nest the value under each segment of the path, innermost last.
+    #[must_use]
+    pub fn to_value(&self) -> serde_json::Value {
+        self.path
+            .iter()
+            .rev()
+            .fold(self.value.clone(), |value, key| {
+                serde_json::Value::Object(serde_json::Map::from_iter([(key.clone(), value)]))
+            })
+    }
 }
 
-impl From<Flavor> for catppuccin::FlavorName {
-    fn from(val: Flavor) -> Self {
-        match val {
-            Flavor::Latte => Self::Latte,
-            Flavor::Frappe => Self::Frappe,
-            Flavor::Macchiato => Self::Macchiato,
-            Flavor::Mocha => Self::Mocha,
+impl Args {
+    /// Parse the command line, keeping `--set` and `--set-json` in the order
+    /// they were given.
+    ///
+    /// clap collects each flag into its own list, so both are merged back
+    /// into `set` by their position on the command line, leaving `set_json`
+    /// empty.
+    #[must_use]
+    pub fn parse_ordered() -> Self {
+        use clap::{CommandFactory as _, FromArgMatches as _};
+
+        let matches = Self::command().get_matches();
+        let mut args = Self::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+        let indices = |id| matches.indices_of(id).into_iter().flatten();
+        let mut sets = indices("set")
+            .zip(std::mem::take(&mut args.set))
+            .chain(indices("set_json").zip(std::mem::take(&mut args.set_json)))
+            .collect::<Vec<_>>();
+        sets.sort_by_key(|(index, _)| *index);
+        args.set = sets.into_iter().map(|(_, set)| set).collect();
+
+        args
+    }
+
+    /// Read overrides from `WHISKERS_*` environment variables.
+    ///
+    /// Environment overrides have the lowest precedence, so any overrides
+    /// given on the command line are applied over them.
+    pub fn with_env_overrides(mut self) -> anyhow::Result<Self> {
+        self.env = EnvOverrides::from_env()?;
+        Ok(self)
+    }
+
+    /// Combine `WHISKERS_OVERRIDES`, `WHISKERS_SET_*`, `--overrides` and
+    /// finally `--set` & `--set-json` into a single map of frontmatter
+    /// overrides, in that order of precedence.
+    #[must_use]
+    pub fn frontmatter_overrides(&self) -> ValueMap {
+        let as_value = |overrides: &ValueMap| {
+            serde_json::Value::Object(overrides.clone().into_iter().collect())
+        };
+
+        // every environment source is applied before any command line source
+        let env = self
+            .env
+            .overrides
+            .iter()
+            .map(as_value)
+            .chain(self.env.set.iter().map(SetOverride::to_value));
+        let cli = self.overrides.iter().map(as_value).chain(
+            self.set
+                .iter()
+                .chain(&self.set_json)
+                .map(SetOverride::to_value),
+        );
+        let result = env.chain(cli).fold(
+            serde_json::Value::Object(serde_json::Map::new()),
+            |result, overrides| merge_values(&result, &overrides),
+        );
+
+        match result {
+            serde_json::Value::Object(map) => map.into_iter().collect(),
+            _ => unreachable!("merging two objects always produces an object"),
+        }
+    }
+
+    /// Ensure `--flavor`, if given, is one of the available flavors.
+    ///
+    /// This can't be checked while parsing, as derived flavors are only known
+    /// once the color overrides have been read.
+    pub fn validate_flavor(&self, flavors: &[String]) -> Result<(), clap::Error> {
+        use clap::{
+            error::{ContextKind, ContextValue, ErrorKind},
+            CommandFactory as _,
+        };
+
+        let Some(ref flavor) = self.flavor else {
+            return Ok(());
+        };
+        if flavors.contains(flavor) {
+            return Ok(());
         }
+
+        let mut err = clap::Error::new(ErrorKind::InvalidValue).with_cmd(&Self::command());
+        err.insert(
+            ContextKind::InvalidArg,
+            ContextValue::String("--flavor <FLAVOR>".to_string()),
+        );
+        err.insert(
+            ContextKind::InvalidValue,
+            ContextValue::String(flavor.clone()),
+        );
+        err.insert(
+            ContextKind::ValidValue,
+            ContextValue::Strings(flavors.to_vec()),
+        );
+        Err(err)
+    }
+
+    /// Combine `WHISKERS_COLOR_OVERRIDES` and every `--color-overrides` into
+    /// one, with later overrides taking precedence.
+    #[must_use]
+    pub fn merged_color_overrides(&self) -> Option<ColorOverrides> {
+        self.env
+            .color_overrides
+            .iter()
+            .chain(&self.color_overrides)
+            .cloned()
+            .reduce(ColorOverrides::merge)
     }
 }
 
-#[derive(Clone, Debug, serde::Deserialize)]
+impl EnvOverrides {
+    fn from_env() -> Result<Self, Error> {
+        let mut result = Self::default();
+        let mut set = Vec::new();
+
+        // non-unicode variables can't be meant for us, so they're ignored
+        for (name, value) in std::env::vars_os()
+            .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        {
+            // treat empty variables as unset, as CI systems often set them that way
+            if value.is_empty() {
+                continue;
+            }
+
+            let with_name = |source| Error::InvalidEnvVar {
+                name: name.clone(),
+                source: Box::new(source),
+            };
+            if name == OVERRIDES_ENV {
+                result.overrides = Some(json_map(&value).map_err(with_name)?);
+            } else if name == COLOR_OVERRIDES_ENV {
+                result.color_overrides = Some(json_map(&value).map_err(with_name)?);
+            } else if let Some(path) = name.strip_prefix(SET_ENV_PREFIX) {
+                let arg = format!("{}={value}", path.replace("__", "."));
+                set.push((name.clone(), set_override(&arg).map_err(with_name)?));
+            }
+        }
+
+        // the environment is unordered, so sort for consistent precedence
+        set.sort_by(|(a, _), (b, _)| a.cmp(b));
+        result.set = set.into_iter().map(|(_, set)| set).collect();
+
+        Ok(result)
+    }
+}
+
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct ColorOverrides {
     #[serde(default)]
     pub all: HashMap<String, String>,
@@ -104,6 +357,132 @@ pub struct ColorOverrides {
     pub macchiato: HashMap<String, String>,
     #[serde(default)]
     pub mocha: HashMap<String, String>,
+    /// New colors to add to every flavor, after the built-in ones
+    #[serde(default)]
+    pub extra: IndexMap<String, String>,
+    /// New flavors derived from existing ones
+    #[serde(default)]
+    pub derived: IndexMap<String, DerivedFlavor>,
+}
+
+/// A flavor defined as a series of operations on a parent flavor.
+///
+/// Unset fields are skipped when serializing, so that merging
+/// [`ColorOverrides`] keeps the values of earlier overrides.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DerivedFlavor {
+    pub parent: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<char>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dark: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub operations: Vec<FlavorOperation>,
+}
+
+/// A single step in building a [`DerivedFlavor`].
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum FlavorOperation {
+    /// Set colors to new values, like a color override
+    Set(HashMap<String, String>),
+    /// Add to colors, like the `add` filter
+    Add(ColorAdjustment),
+    /// Subtract from colors, like the `sub` filter
+    Sub(ColorAdjustment),
+    /// Modify colors, like the `mod` filter
+    Mod(ColorAdjustment),
+}
+
+/// Arguments for an add, sub or mod [`FlavorOperation`].
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColorAdjustment {
+    /// The colors to adjust, or every color if not given
+    pub colors: Option<Vec<String>>,
+    pub hue: Option<i32>,
+    pub saturation: Option<u8>,
+    pub lightness: Option<u8>,
+    pub opacity: Option<f32>,
+    pub chroma: Option<f32>,
+    pub perceptual_lightness: Option<u8>,
+}
+
+impl ColorOverrides {
+    /// Deep-merge another set of color overrides over this one.
+    ///
+    /// A derived flavor given in both keeps any field the later one doesn't
+    /// set, while its `operations`, if given, replace the earlier ones.
+    ///
+    /// # Panics
+    ///
+    /// Never, as both sides are valid color overrides and so is their merge.
+    #[must_use]
+    pub fn merge(self, other: Self) -> Self {
+        let to_value = |overrides: Self| {
+            serde_json::to_value(overrides)
+                .expect("color overrides are guaranteed to be valid json")
+        };
+        serde_json::from_value(merge_values(&to_value(self), &to_value(other)))
+            .expect("merging two valid color overrides always gives valid color overrides")
+    }
+}
+
+/// A palette read from `--palette`, in the same shape as [`crate::models::Palette`].
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomPalette {
+    pub flavors: IndexMap<String, CustomFlavor>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomFlavor {
+    pub name: String,
+    pub emoji: char,
+    pub dark: bool,
+    pub colors: IndexMap<String, CustomColor>,
+}
+
+/// A color in a custom palette, either as a bare color string or as an object
+/// with a `hex` color string and optional `name` and `accent` fields.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum CustomColor {
+    Value(String),
+    Full {
+        hex: String,
+        name: Option<String>,
+        accent: Option<bool>,
+    },
+}
+
+impl CustomColor {
+    #[must_use]
+    pub fn value(&self) -> &str {
+        match self {
+            Self::Value(value) | Self::Full { hex: value, .. } => value,
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::Value(_) => None,
+            Self::Full { name, .. } => name.as_deref(),
+        }
+    }
+
+    #[must_use]
+    pub const fn accent(&self) -> Option<bool> {
+        match self {
+            Self::Value(_) => None,
+            Self::Full { accent, .. } => *accent,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, clap::ValueEnum)]
@@ -118,21 +497,136 @@ pub enum OutputFormat {
     MarkdownTable,
 }
 
+/// Swatch file formats supported by `--export`.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// GIMP palette (`.gpl`)
+    Gpl,
+    /// Adobe Swatch Exchange (`.ase`)
+    Ase,
+    /// Procreate swatches (`.swatches`)
+    Procreate,
+    /// Sketch Palettes plugin palette (`.sketchpalette`)
+    Sketch,
+    /// CSS custom properties (`.css`)
+    Css,
+}
+
 fn json_map<T>(s: &str) -> Result<T, Error>
 where
     T: serde::de::DeserializeOwned,
 {
-    if Path::new(s).is_file() {
-        let s = std::fs::read_to_string(s).map_err(|e| Error::ReadFile {
+    let path = Path::new(s);
+    if path.is_file() {
+        let contents = std::fs::read_to_string(path).map_err(|e| Error::ReadFile {
             path: s.to_string(),
             source: e,
         })?;
-        serde_json::from_str(&s).map_err(|e| Error::InvalidJsonFileArg {
-            message: e.to_string(),
-        })
+
+        // pick a format based on the file extension, defaulting to JSON
+        match path.extension().and_then(|e| e.to_str()) {
+            // serde_yaml expects enums as YAML tags, so we go through a JSON
+            // value to accept the same `- set: {...}` shape as JSON & TOML.
+            Some("yaml" | "yml") => serde_yaml::from_str::<serde_json::Value>(&contents)
+                .map_err(|e| e.to_string())
+                .and_then(|v| serde_json::from_value(v).map_err(|e| e.to_string()))
+                .map_err(|message| Error::InvalidYamlFileArg { message }),
+            Some("toml") => toml::from_str(&contents).map_err(|e| Error::InvalidTomlFileArg {
+                message: e.to_string(),
+            }),
+            _ => serde_json::from_str(&contents).map_err(|e| Error::InvalidJsonFileArg {
+                message: e.to_string(),
+            }),
+        }
     } else {
         serde_json::from_str(s).map_err(|e| Error::InvalidJsonLiteralArg {
             message: e.to_string(),
         })
     }
 }
+
+fn split_set_arg(s: &str) -> Result<(Vec<String>, &str), Error> {
+    let invalid = |message: &str| Error::InvalidSetArg {
+        arg: s.to_string(),
+        message: message.to_string(),
+    };
+
+    let (path, value) = s
+        .split_once('=')
+        .ok_or_else(|| invalid("expected PATH=VALUE"))?;
+    let path = path
+        .split('.')
+        .map(|key| {
+            if key.is_empty() {
+                Err(invalid("path must not contain empty keys"))
+            } else {
+                Ok(key.to_string())
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((path, value))
+}
+
+fn set_override(s: &str) -> Result<SetOverride, Error> {
+    let (path, value) = split_set_arg(s)?;
+
+    // infer the type of the value, falling back to a string
+    let value = value
+        .parse::<bool>()
+        .map(serde_json::Value::Bool)
+        .or_else(|_| value.parse::<i64>().map(serde_json::Value::from))
+        .ok()
+        .or_else(|| {
+            value
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(serde_json::Value::Number)
+        })
+        .unwrap_or_else(|| serde_json::Value::String(value.to_string()));
+
+    Ok(SetOverride { path, value })
+}
+
+fn set_json_override(s: &str) -> Result<SetOverride, Error> {
+    let (path, value) = split_set_arg(s)?;
+    let value = serde_json::from_str(value).map_err(|e| Error::InvalidSetArg {
+        arg: s.to_string(),
+        message: e.to_string(),
+    })?;
+    Ok(SetOverride { path, value })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_set_override_types() {
+        let value = |s| set_override(s).expect("test value is always valid").value;
+        assert_eq!(value("a=true"), json!(true));
+        assert_eq!(value("a=42"), json!(42));
+        assert_eq!(value("a=0.5"), json!(0.5));
+        assert_eq!(value("a=blue"), json!("blue"));
+        assert_eq!(value("a="), json!(""));
+        assert_eq!(value("a=x=y"), json!("x=y"));
+        assert_eq!(value("a=nan"), json!("nan"));
+    }
+
+    #[test]
+    fn test_set_override_nesting() {
+        let set = set_override("a.b.c=1").expect("test value is always valid");
+        assert_eq!(set.to_value(), json!({"a": {"b": {"c": 1}}}));
+    }
+
+    #[test]
+    fn test_set_override_invalid() {
+        assert!(set_override("a").is_err());
+        assert!(set_override("a..b=1").is_err());
+        assert!(set_override("=1").is_err());
+        assert!(set_json_override("a=not json").is_err());
+    }
+}
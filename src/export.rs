@@ -0,0 +1,256 @@
+use std::{
+    fmt::Write as _,
+    io::{Cursor, Write as _},
+};
+
+use itertools::Itertools as _;
+
+use crate::{
+    cli::ExportFormat,
+    models::{Color, Flavor},
+};
+
+/// Procreate silently drops any swatches past the 30th in a palette.
+const PROCREATE_MAX_SWATCHES: usize = 30;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(
+        "Procreate swatches hold a single palette, so a flavor must be selected with --flavor"
+    )]
+    MultiplePalettes,
+    #[error("Procreate palettes can hold at most {PROCREATE_MAX_SWATCHES} colors, but flavor \"{flavor}\" has {count}")]
+    TooManySwatches { flavor: String, count: usize },
+    #[error("Failed to write Procreate swatches archive")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Failed to write export")]
+    Io(#[from] std::io::Error),
+}
+
+/// Export the given flavors as a swatch file in the given format.
+///
+/// Formats that can only hold a single palette (GIMP, Sketch, CSS) prefix
+/// every color with its flavor when more than one flavor is exported.
+pub fn export(flavors: &[&Flavor], format: ExportFormat) -> Result<Vec<u8>, Error> {
+    match format {
+        ExportFormat::Gpl => Ok(gpl(flavors).into_bytes()),
+        ExportFormat::Ase => Ok(ase(flavors)),
+        ExportFormat::Procreate => procreate(flavors),
+        ExportFormat::Sketch => Ok(sketch(flavors).into_bytes()),
+        ExportFormat::Css => Ok(css(flavors).into_bytes()),
+    }
+}
+
+fn palette_name(flavors: &[&Flavor]) -> String {
+    match flavors {
+        [flavor] => format!("Catppuccin {}", flavor.name),
+        _ => "Catppuccin".to_string(),
+    }
+}
+
+/// the colors of every flavor, with names prefixed by the flavor name if
+/// there's more than one flavor.
+fn named_colors<'a>(flavors: &[&'a Flavor]) -> Vec<(String, &'a Color)> {
+    flavors
+        .iter()
+        .flat_map(|flavor| {
+            flavor.colors.values().map(move |color| {
+                let name = if flavors.len() > 1 {
+                    format!("{} {}", flavor.name, color.name)
+                } else {
+                    color.name.clone()
+                };
+                (name, color)
+            })
+        })
+        .collect()
+}
+
+fn unit(channel: u8) -> f64 {
+    f64::from(channel) / 255.0
+}
+
+/// GIMP palette, with one `R G B\tName` line per color.
+fn gpl(flavors: &[&Flavor]) -> String {
+    let mut out = format!(
+        "GIMP Palette\nName: {}\nColumns: 0\n#\n",
+        palette_name(flavors)
+    );
+    for (name, color) in named_colors(flavors) {
+        writeln!(
+            out,
+            "{:3} {:3} {:3}\t{name}",
+            color.rgb.r, color.rgb.g, color.rgb.b
+        )
+        .expect("writing to a string is infallible");
+    }
+    out
+}
+
+/// Adobe Swatch Exchange, with one group per flavor.
+///
+/// All values are big-endian. The file is a header followed by a list of
+/// blocks, each being a block type, a byte length and the block data.
+fn ase(flavors: &[&Flavor]) -> Vec<u8> {
+    const GROUP_START: u16 = 0xC001;
+    const GROUP_END: u16 = 0xC002;
+    const COLOR_ENTRY: u16 = 0x0001;
+    const COLOR_TYPE_GLOBAL: u16 = 0;
+
+    // names are null-terminated UTF-16 prefixed by their length in code units
+    fn ase_name(name: &str) -> Vec<u8> {
+        let units = name.encode_utf16().chain([0]).collect::<Vec<_>>();
+        let mut data = (units.len() as u16).to_be_bytes().to_vec();
+        data.extend(units.iter().flat_map(|u| u.to_be_bytes()));
+        data
+    }
+
+    fn block(out: &mut Vec<u8>, kind: u16, data: &[u8]) {
+        out.extend(kind.to_be_bytes());
+        out.extend((data.len() as u32).to_be_bytes());
+        out.extend(data);
+    }
+
+    let mut blocks = Vec::new();
+    let mut count: u32 = 0;
+    for flavor in flavors {
+        block(
+            &mut blocks,
+            GROUP_START,
+            &ase_name(&palette_name(&[flavor])),
+        );
+        for color in flavor.colors.values() {
+            let mut data = ase_name(&color.name);
+            data.extend(b"RGB ");
+            for channel in [color.rgb.r, color.rgb.g, color.rgb.b] {
+                data.extend((unit(channel) as f32).to_be_bytes());
+            }
+            data.extend(COLOR_TYPE_GLOBAL.to_be_bytes());
+            block(&mut blocks, COLOR_ENTRY, &data);
+        }
+        block(&mut blocks, GROUP_END, &[]);
+        count += flavor.colors.len() as u32 + 2;
+    }
+
+    let mut out = b"ASEF".to_vec();
+    out.extend(1u16.to_be_bytes());
+    out.extend(0u16.to_be_bytes());
+    out.extend(count.to_be_bytes());
+    out.extend(blocks);
+    out
+}
+
+/// Procreate `.swatches` file, a zip archive holding a `Swatches.json` array
+/// with a single palette of colors in HSB.
+fn procreate(flavors: &[&Flavor]) -> Result<Vec<u8>, Error> {
+    let [flavor] = flavors else {
+        return Err(Error::MultiplePalettes);
+    };
+    if flavor.colors.len() > PROCREATE_MAX_SWATCHES {
+        return Err(Error::TooManySwatches {
+            flavor: flavor.identifier.clone(),
+            count: flavor.colors.len(),
+        });
+    }
+
+    let swatches = flavor
+        .colors
+        .values()
+        .map(|color| {
+            let (hue, saturation, brightness) = hsb(color);
+            serde_json::json!({
+                "hue": hue,
+                "saturation": saturation,
+                "brightness": brightness,
+                "alpha": unit(color.opacity),
+                "colorSpace": 0,
+            })
+        })
+        .collect::<Vec<_>>();
+    let palettes = [serde_json::json!({
+        "name": palette_name(flavors),
+        "swatches": swatches,
+    })];
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file(
+        "Swatches.json",
+        zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored),
+    )?;
+    zip.write_all(
+        serde_json::to_string(&palettes)
+            .expect("palettes are guaranteed to be valid json")
+            .as_bytes(),
+    )?;
+    Ok(zip.finish()?.into_inner())
+}
+
+/// convert a color to hue, saturation & brightness, all in the range `0..=1`.
+fn hsb(color: &Color) -> (f64, f64, f64) {
+    let [r, g, b] = [color.rgb.r, color.rgb.g, color.rgb.b].map(unit);
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if (max - r).abs() < f64::EPSILON {
+        ((g - b) / delta).rem_euclid(6.0)
+    } else if (max - g).abs() < f64::EPSILON {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    } / 6.0;
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+
+    (hue, saturation, max)
+}
+
+/// Sketch Palettes plugin `.sketchpalette` file.
+fn sketch(flavors: &[&Flavor]) -> String {
+    let colors = named_colors(flavors)
+        .into_iter()
+        .map(|(name, color)| {
+            serde_json::json!({
+                "name": name,
+                "red": unit(color.rgb.r),
+                "green": unit(color.rgb.g),
+                "blue": unit(color.rgb.b),
+                "alpha": unit(color.opacity),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    serde_json::to_string_pretty(&serde_json::json!({
+        "compatibleVersion": "2.0",
+        "pluginVersion": "2.22",
+        "colors": colors,
+    }))
+    .expect("palette is guaranteed to be valid json")
+        + "\n"
+}
+
+/// CSS custom properties named `--ctp-{flavor}-{color}`.
+fn css(flavors: &[&Flavor]) -> String {
+    let properties = flavors
+        .iter()
+        .map(|flavor| {
+            flavor
+                .colors
+                .values()
+                .map(|color| {
+                    let alpha = if color.opacity == 0xFF {
+                        String::new()
+                    } else {
+                        format!("{:02x}", color.opacity)
+                    };
+                    format!(
+                        "  --ctp-{}-{}: #{:02x}{:02x}{:02x}{alpha};",
+                        flavor.identifier, color.identifier, color.rgb.r, color.rgb.g, color.rgb.b
+                    )
+                })
+                .join("\n")
+        })
+        .join("\n\n");
+
+    format!(":root {{\n{properties}\n}}\n")
+}
@@ -5,7 +5,17 @@ use std::{
 
 use base64::Engine as _;
 
-use crate::models::Color;
+use crate::models::{BlendMode, Color, Deficiency, Flavor, HexFormat, Interpolation};
+
+/// how far towards white & black the outermost colors of a `ramp` are mixed.
+const RAMP_AMOUNT: f64 = 0.8;
+
+/// the colors of the flavor being rendered that `readable_on` picks from if
+/// no candidates are given.
+const READABLE_ON_CANDIDATES: [&str; 3] = ["text", "base", "crust"];
+
+/// the WCAG 2.x AA minimum contrast ratio for normal text.
+const READABLE_ON_MIN_RATIO: f64 = 4.5;
 
 pub fn mix(
     value: &tera::Value,
@@ -23,11 +33,33 @@ pub fn mix(
         .as_f64()
         .ok_or_else(|| tera::Error::msg("blend amount must be a number"))?;
 
-    let result = Color::mix(&base, &blend, amount)?;
+    let result = Color::mix(&base, &blend, amount);
 
     Ok(tera::to_value(result)?)
 }
 
+pub fn flatten(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> Result<tera::Value, tera::Error> {
+    let color: Color = tera::from_value(value.clone())?;
+    let background: Color = tera::from_value(
+        args.get("on")
+            .ok_or_else(|| tera::Error::msg("background color is required"))?
+            .clone(),
+    )?;
+    let mode: BlendMode = args
+        .get("mode")
+        .map(|mode| {
+            tera::from_value(mode.clone()).map_err(|_| {
+                tera::Error::msg("mode must be one of normal, multiply, screen or overlay")
+            })
+        })
+        .transpose()?
+        .unwrap_or_default();
+    Ok(tera::to_value(color.flatten(&background, mode))?)
+}
+
 pub fn modify(
     value: &tera::Value,
     args: &HashMap<String, tera::Value>,
@@ -35,16 +67,22 @@ pub fn modify(
     let color: Color = tera::from_value(value.clone())?;
     if let Some(hue) = args.get("hue") {
         let hue = tera::from_value(hue.clone())?;
-        Ok(tera::to_value(color.mod_hue(hue)?)?)
+        Ok(tera::to_value(color.mod_hue(hue))?)
     } else if let Some(saturation) = args.get("saturation") {
         let saturation = tera::from_value(saturation.clone())?;
-        Ok(tera::to_value(color.mod_saturation(saturation)?)?)
+        Ok(tera::to_value(color.mod_saturation(saturation))?)
     } else if let Some(lightness) = args.get("lightness") {
         let lightness = tera::from_value(lightness.clone())?;
-        Ok(tera::to_value(color.mod_lightness(lightness)?)?)
+        Ok(tera::to_value(color.mod_lightness(lightness))?)
     } else if let Some(opacity) = args.get("opacity") {
         let opacity = tera::from_value(opacity.clone())?;
-        Ok(tera::to_value(color.mod_opacity(opacity)?)?)
+        Ok(tera::to_value(color.mod_opacity(opacity))?)
+    } else if let Some(chroma) = args.get("chroma") {
+        let chroma = tera::from_value(chroma.clone())?;
+        Ok(tera::to_value(color.mod_chroma(chroma))?)
+    } else if let Some(lightness) = args.get("perceptual_lightness") {
+        let lightness = tera::from_value(lightness.clone())?;
+        Ok(tera::to_value(color.mod_perceptual_lightness(lightness))?)
     } else {
         Ok(value.clone())
     }
@@ -57,16 +95,22 @@ pub fn add(
     let color: Color = tera::from_value(value.clone())?;
     if let Some(hue) = args.get("hue") {
         let hue = tera::from_value(hue.clone())?;
-        Ok(tera::to_value(color.add_hue(hue)?)?)
+        Ok(tera::to_value(color.add_hue(hue))?)
     } else if let Some(saturation) = args.get("saturation") {
         let saturation = tera::from_value(saturation.clone())?;
-        Ok(tera::to_value(color.add_saturation(saturation)?)?)
+        Ok(tera::to_value(color.add_saturation(saturation))?)
     } else if let Some(lightness) = args.get("lightness") {
         let lightness = tera::from_value(lightness.clone())?;
-        Ok(tera::to_value(color.add_lightness(lightness)?)?)
+        Ok(tera::to_value(color.add_lightness(lightness))?)
     } else if let Some(opacity) = args.get("opacity") {
         let opacity = tera::from_value(opacity.clone())?;
-        Ok(tera::to_value(color.add_opacity(opacity)?)?)
+        Ok(tera::to_value(color.add_opacity(opacity))?)
+    } else if let Some(chroma) = args.get("chroma") {
+        let chroma = tera::from_value(chroma.clone())?;
+        Ok(tera::to_value(color.add_chroma(chroma))?)
+    } else if let Some(lightness) = args.get("perceptual_lightness") {
+        let lightness = tera::from_value(lightness.clone())?;
+        Ok(tera::to_value(color.add_perceptual_lightness(lightness))?)
     } else {
         Ok(value.clone())
     }
@@ -79,21 +123,66 @@ pub fn sub(
     let color: Color = tera::from_value(value.clone())?;
     if let Some(hue) = args.get("hue") {
         let hue = tera::from_value(hue.clone())?;
-        Ok(tera::to_value(color.sub_hue(hue)?)?)
+        Ok(tera::to_value(color.sub_hue(hue))?)
     } else if let Some(saturation) = args.get("saturation") {
         let saturation = tera::from_value(saturation.clone())?;
-        Ok(tera::to_value(color.sub_saturation(saturation)?)?)
+        Ok(tera::to_value(color.sub_saturation(saturation))?)
     } else if let Some(lightness) = args.get("lightness") {
         let lightness = tera::from_value(lightness.clone())?;
-        Ok(tera::to_value(color.sub_lightness(lightness)?)?)
+        Ok(tera::to_value(color.sub_lightness(lightness))?)
     } else if let Some(opacity) = args.get("opacity") {
         let opacity = tera::from_value(opacity.clone())?;
-        Ok(tera::to_value(color.sub_opacity(opacity)?)?)
+        Ok(tera::to_value(color.sub_opacity(opacity))?)
+    } else if let Some(chroma) = args.get("chroma") {
+        let chroma = tera::from_value(chroma.clone())?;
+        Ok(tera::to_value(color.sub_chroma(chroma))?)
+    } else if let Some(lightness) = args.get("perceptual_lightness") {
+        let lightness = tera::from_value(lightness.clone())?;
+        Ok(tera::to_value(color.sub_perceptual_lightness(lightness))?)
     } else {
         Ok(value.clone())
     }
 }
 
+pub fn ramp(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> Result<tera::Value, tera::Error> {
+    let color: Color = tera::from_value(value.clone())?;
+    // without either, the ramp would just be the color itself
+    if !args.contains_key("tints") && !args.contains_key("shades") {
+        return Err(tera::Error::msg(
+            "at least one of `tints` and `shades` is required",
+        ));
+    }
+    let count = |key: &str| -> Result<u32, tera::Error> {
+        Ok(args
+            .get(key)
+            .map(|v| tera::from_value(v.clone()))
+            .transpose()?
+            .unwrap_or_default())
+    };
+    let amount: f64 = args
+        .get("amount")
+        .map(|v| tera::from_value(v.clone()))
+        .transpose()?
+        .unwrap_or(RAMP_AMOUNT);
+    let space = Interpolation::from_args(args)?;
+    Ok(tera::to_value(color.ramp(
+        count("tints")?,
+        count("shades")?,
+        amount,
+        space,
+    ))?)
+}
+
+pub fn to_color(
+    value: &tera::Value,
+    _args: &HashMap<String, tera::Value>,
+) -> Result<tera::Value, tera::Error> {
+    Ok(tera::to_value(Color::from_value(value)?)?)
+}
+
 pub fn urlencode_lzma(
     value: &tera::Value,
     _args: &HashMap<String, tera::Value>,
@@ -144,10 +233,17 @@ pub fn css_rgb(
 
 pub fn hex(
     value: &tera::Value,
-    _args: &HashMap<String, tera::Value>,
+    args: &HashMap<String, tera::Value>,
 ) -> Result<tera::Value, tera::Error> {
     let color: Color = tera::from_value(value.clone())?;
-    Ok(tera::to_value(color.hex)?)
+    match args.get("format") {
+        Some(format) => {
+            let format = HexFormat::parse(&tera::from_value::<String>(format.clone())?)
+                .map_err(|e| tera::Error::msg(e.to_string()))?;
+            Ok(tera::to_value(format.format(&color.rgb, color.opacity))?)
+        }
+        None => Ok(tera::to_value(color.hex)?),
+    }
 }
 
 pub fn css_rgba(
@@ -168,6 +264,38 @@ pub fn css_hsl(
     Ok(tera::to_value(color.to_string())?)
 }
 
+pub fn css_hwb(
+    value: &tera::Value,
+    _args: &HashMap<String, tera::Value>,
+) -> Result<tera::Value, tera::Error> {
+    let color: Color = tera::from_value(value.clone())?;
+    Ok(tera::to_value(color.css_hwb())?)
+}
+
+pub fn css_lab(
+    value: &tera::Value,
+    _args: &HashMap<String, tera::Value>,
+) -> Result<tera::Value, tera::Error> {
+    let color: Color = tera::from_value(value.clone())?;
+    Ok(tera::to_value(color.css_lab())?)
+}
+
+pub fn css_oklab(
+    value: &tera::Value,
+    _args: &HashMap<String, tera::Value>,
+) -> Result<tera::Value, tera::Error> {
+    let color: Color = tera::from_value(value.clone())?;
+    Ok(tera::to_value(color.css_oklab())?)
+}
+
+pub fn css_oklch(
+    value: &tera::Value,
+    _args: &HashMap<String, tera::Value>,
+) -> Result<tera::Value, tera::Error> {
+    let color: Color = tera::from_value(value.clone())?;
+    Ok(tera::to_value(color.css_oklch())?)
+}
+
 pub fn css_hsla(
     value: &tera::Value,
     _args: &HashMap<String, tera::Value>,
@@ -176,3 +304,133 @@ pub fn css_hsla(
     let color: farver::HSLA = (&color).into();
     Ok(tera::to_value(color.to_string())?)
 }
+
+pub fn rgb_array(
+    value: &tera::Value,
+    _args: &HashMap<String, tera::Value>,
+) -> Result<tera::Value, tera::Error> {
+    let color: Color = tera::from_value(value.clone())?;
+    Ok(tera::to_value(color.rgb.channels)?)
+}
+
+pub fn simulate(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> Result<tera::Value, tera::Error> {
+    let color: Color = tera::from_value(value.clone())?;
+    let deficiency: Deficiency = tera::from_value(
+        args.get("deficiency")
+            .ok_or_else(|| tera::Error::msg("deficiency is required"))?
+            .clone(),
+    )
+    .map_err(|_| {
+        tera::Error::msg(
+            "deficiency must be one of protanopia, deuteranopia, tritanopia or achromatopsia",
+        )
+    })?;
+    Ok(tera::to_value(color.simulate(deficiency))?)
+}
+
+pub fn xterm256(
+    value: &tera::Value,
+    _args: &HashMap<String, tera::Value>,
+) -> Result<tera::Value, tera::Error> {
+    let color: Color = tera::from_value(value.clone())?;
+    Ok(tera::to_value(color.xterm256())?)
+}
+
+pub fn ansi16(
+    value: &tera::Value,
+    _args: &HashMap<String, tera::Value>,
+) -> Result<tera::Value, tera::Error> {
+    let color: Color = tera::from_value(value.clone())?;
+    Ok(tera::to_value(color.ansi16())?)
+}
+
+pub fn ansi(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> Result<tera::Value, tera::Error> {
+    let color: Color = tera::from_value(value.clone())?;
+    let background = match args.get("layer").map(tera::Value::as_str) {
+        None | Some(Some("fg")) => false,
+        Some(Some("bg")) => true,
+        Some(_) => return Err(tera::Error::msg("layer must be one of fg or bg")),
+    };
+    // 256 and 16 are numbers, so they may be given with or without quotes
+    let mode = match args.get("mode") {
+        None => "truecolor".to_string(),
+        Some(tera::Value::String(mode)) => mode.clone(),
+        Some(tera::Value::Number(mode)) => mode.to_string(),
+        Some(_) => String::new(),
+    };
+
+    let parameters = match mode.as_str() {
+        "truecolor" => format!(
+            "{};2;{};{};{}",
+            if background { 48 } else { 38 },
+            color.rgb.r,
+            color.rgb.g,
+            color.rgb.b
+        ),
+        "256" => format!(
+            "{};5;{}",
+            if background { 48 } else { 38 },
+            color.xterm256()
+        ),
+        "16" => {
+            // the bright colors (8-15) have their own range of codes
+            let index = color.ansi16();
+            let base = match (background, index < 8) {
+                (false, true) => 30,
+                (false, false) => 90 - 8,
+                (true, true) => 40,
+                (true, false) => 100 - 8,
+            };
+            (base + index).to_string()
+        }
+        _ => return Err(tera::Error::msg("mode must be one of truecolor, 256 or 16")),
+    };
+    Ok(tera::to_value(format!("\x1b[{parameters}m"))?)
+}
+
+pub fn readable_on_handler(
+    flavor: Option<&Flavor>,
+) -> impl Fn(&tera::Value, &HashMap<String, tera::Value>) -> Result<tera::Value, tera::Error> {
+    let default_candidates = flavor.map(|flavor| {
+        READABLE_ON_CANDIDATES
+            .iter()
+            .filter_map(|identifier| flavor.colors.get(*identifier).cloned())
+            .collect::<Vec<_>>()
+    });
+    move |value, args| -> Result<tera::Value, tera::Error> {
+        let background: Color = tera::from_value(value.clone())?;
+        let min_ratio: f64 = args
+            .get("min_ratio")
+            .map(|v| tera::from_value(v.clone()))
+            .transpose()?
+            .unwrap_or(READABLE_ON_MIN_RATIO);
+        let candidates: Vec<Color> = match args.get("candidates") {
+            Some(candidates) => tera::from_value(candidates.clone())?,
+            None => default_candidates.clone().ok_or_else(|| {
+                tera::Error::msg("no flavor is being rendered, so `candidates` must be given")
+            })?,
+        };
+
+        // pick the candidate with the highest contrast, preferring earlier
+        // candidates on ties.
+        let (ratio, best) = candidates
+            .into_iter()
+            .map(|candidate| (candidate.contrast_ratio(&background), candidate))
+            .reduce(|best, next| if next.0 > best.0 { next } else { best })
+            .ok_or_else(|| tera::Error::msg("candidates must not be empty"))?;
+        if ratio < min_ratio {
+            return Err(tera::Error::msg(format!(
+                "No candidate is readable on {}: the best contrast ratio is {ratio:.2}, but at least {min_ratio} is required",
+                background.identifier
+            )));
+        }
+
+        Ok(tera::to_value(best)?)
+    }
+}
@@ -1,5 +1,13 @@
 use detect_newline_style::LineEnding;
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::context::merge_values;
+
+const OPTIONS_SECTION: &str = "whiskers";
+const EXTENDS_KEY: &str = "extends";
 
 #[derive(Debug)]
 pub struct Document {
@@ -15,6 +23,27 @@ pub enum Error {
         column: usize,
         message: String,
     },
+
+    #[error("Invalid `extends` value: must be a path or a list of paths")]
+    InvalidExtends,
+
+    #[error("Failed to read extended file: {path}")]
+    ReadExtends {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Invalid YAML in extended file {path} (L{line}:{column}) : {message}")]
+    InvalidExtendsYaml {
+        path: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+
+    #[error("Extended file {path} is already being extended, this would create a cycle")]
+    ExtendsCycle { path: String },
 }
 
 pub fn parse(input: &str) -> Result<Document, Error> {
@@ -36,6 +65,100 @@ pub fn parse(input: &str) -> Result<Document, Error> {
     })
 }
 
+/// Resolve the `whiskers.extends` key of the given frontmatter.
+///
+/// Each extended YAML file is loaded relative to `directory`, has its own
+/// `extends` resolved relative to its own location, and is then merged in
+/// order underneath the given frontmatter. Later files take precedence over
+/// earlier ones, and the given frontmatter takes precedence over all of them.
+pub fn resolve_extends(
+    frontmatter: HashMap<String, tera::Value>,
+    directory: &Path,
+) -> Result<HashMap<String, tera::Value>, Error> {
+    resolve_extends_inner(frontmatter, directory, &mut Vec::new())
+}
+
+fn resolve_extends_inner(
+    mut frontmatter: HashMap<String, tera::Value>,
+    directory: &Path,
+    stack: &mut Vec<PathBuf>,
+) -> Result<HashMap<String, tera::Value>, Error> {
+    let paths = take_extends(&mut frontmatter)?;
+    if paths.is_empty() {
+        return Ok(frontmatter);
+    }
+
+    let mut base = tera::Value::Object(tera::Map::new());
+    for path in paths {
+        let path = directory.join(path);
+        let canonical = path.canonicalize().map_err(|e| Error::ReadExtends {
+            path: path.display().to_string(),
+            source: e,
+        })?;
+        if stack.contains(&canonical) {
+            return Err(Error::ExtendsCycle {
+                path: path.display().to_string(),
+            });
+        }
+
+        let extended = read_extends_file(&canonical)?;
+        stack.push(canonical.clone());
+        let extended = resolve_extends_inner(
+            extended,
+            canonical.parent().expect("file path must have a parent"),
+            stack,
+        )?;
+        stack.pop();
+
+        base = merge_values(&base, &to_object(extended));
+    }
+
+    let merged = merge_values(&base, &to_object(frontmatter));
+    Ok(match merged {
+        tera::Value::Object(map) => map.into_iter().collect(),
+        _ => unreachable!("merging two objects always produces an object"),
+    })
+}
+
+/// remove `whiskers.extends` from the frontmatter, returning the paths it held.
+fn take_extends(frontmatter: &mut HashMap<String, tera::Value>) -> Result<Vec<String>, Error> {
+    let Some(tera::Value::Object(options)) = frontmatter.get_mut(OPTIONS_SECTION) else {
+        return Ok(Vec::new());
+    };
+
+    match options.remove(EXTENDS_KEY) {
+        None | Some(tera::Value::Null) => Ok(Vec::new()),
+        Some(tera::Value::String(path)) => Ok(vec![path]),
+        Some(value @ tera::Value::Array(_)) => {
+            tera::from_value(value).map_err(|_| Error::InvalidExtends)
+        }
+        Some(_) => Err(Error::InvalidExtends),
+    }
+}
+
+fn read_extends_file(path: &Path) -> Result<HashMap<String, tera::Value>, Error> {
+    let contents = std::fs::read_to_string(path).map_err(|e| Error::ReadExtends {
+        path: path.display().to_string(),
+        source: e,
+    })?;
+
+    // an empty file is valid, it just doesn't contribute anything
+    if contents.trim().is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    serde_yaml::from_str(&contents).map_err(|e| Error::InvalidExtendsYaml {
+        path: path.display().to_string(),
+        line: e.location().map(|l| l.line()).unwrap_or_default(),
+        column: e.location().map(|l| l.column()).unwrap_or_default(),
+        message: e.to_string(),
+    })
+}
+
+fn to_object(map: HashMap<String, tera::Value>) -> tera::Value {
+    tera::Value::Object(map.into_iter().collect())
+}
+
 fn split(template: &str) -> Option<(&str, &str)> {
     // we consider a template to possibly have frontmatter iff:
     // * line 0 is "---"
@@ -4,7 +4,7 @@ use std::{
     path::PathBuf,
 };
 
-use crate::models::Color;
+use crate::models::{Color, Interpolation};
 
 pub fn if_fn(args: &HashMap<String, tera::Value>) -> Result<tera::Value, tera::Error> {
     let cond = args
@@ -62,6 +62,42 @@ pub fn css_hsl(args: &HashMap<String, tera::Value>) -> Result<tera::Value, tera:
     Ok(tera::to_value(color.to_string())?)
 }
 
+pub fn css_hwb(args: &HashMap<String, tera::Value>) -> Result<tera::Value, tera::Error> {
+    let color: Color = tera::from_value(
+        args.get("color")
+            .ok_or_else(|| tera::Error::msg("color is required"))?
+            .clone(),
+    )?;
+    Ok(tera::to_value(color.css_hwb())?)
+}
+
+pub fn css_lab(args: &HashMap<String, tera::Value>) -> Result<tera::Value, tera::Error> {
+    let color: Color = tera::from_value(
+        args.get("color")
+            .ok_or_else(|| tera::Error::msg("color is required"))?
+            .clone(),
+    )?;
+    Ok(tera::to_value(color.css_lab())?)
+}
+
+pub fn css_oklab(args: &HashMap<String, tera::Value>) -> Result<tera::Value, tera::Error> {
+    let color: Color = tera::from_value(
+        args.get("color")
+            .ok_or_else(|| tera::Error::msg("color is required"))?
+            .clone(),
+    )?;
+    Ok(tera::to_value(color.css_oklab())?)
+}
+
+pub fn css_oklch(args: &HashMap<String, tera::Value>) -> Result<tera::Value, tera::Error> {
+    let color: Color = tera::from_value(
+        args.get("color")
+            .ok_or_else(|| tera::Error::msg("color is required"))?
+            .clone(),
+    )?;
+    Ok(tera::to_value(color.css_oklch())?)
+}
+
 pub fn css_hsla(args: &HashMap<String, tera::Value>) -> Result<tera::Value, tera::Error> {
     let color: Color = tera::from_value(
         args.get("color")
@@ -72,6 +108,123 @@ pub fn css_hsla(args: &HashMap<String, tera::Value>) -> Result<tera::Value, tera
     Ok(tera::to_value(color.to_string())?)
 }
 
+pub fn rgb_array(args: &HashMap<String, tera::Value>) -> Result<tera::Value, tera::Error> {
+    let color: Color = tera::from_value(
+        args.get("color")
+            .ok_or_else(|| tera::Error::msg("color is required"))?
+            .clone(),
+    )?;
+    Ok(tera::to_value(color.rgb.channels)?)
+}
+
+pub fn contrast(args: &HashMap<String, tera::Value>) -> Result<tera::Value, tera::Error> {
+    let a: Color = tera::from_value(
+        args.get("a")
+            .ok_or_else(|| tera::Error::msg("a is required"))?
+            .clone(),
+    )?;
+    let b: Color = tera::from_value(
+        args.get("b")
+            .ok_or_else(|| tera::Error::msg("b is required"))?
+            .clone(),
+    )?;
+    Ok(tera::to_value(a.contrast_ratio(&b))?)
+}
+
+pub fn apca(args: &HashMap<String, tera::Value>) -> Result<tera::Value, tera::Error> {
+    let (text, background) = apca_colors(args)?;
+    Ok(tera::to_value(text.apca_contrast(&background))?)
+}
+
+pub fn apca_readable(args: &HashMap<String, tera::Value>) -> Result<tera::Value, tera::Error> {
+    let (text, background) = apca_colors(args)?;
+    let size: f64 = tera::from_value(
+        args.get("size")
+            .ok_or_else(|| tera::Error::msg("size is required"))?
+            .clone(),
+    )?;
+    let weight: u16 = args
+        .get("weight")
+        .map(|v| tera::from_value(v.clone()))
+        .transpose()?
+        .unwrap_or(400);
+    let lc = text.apca_contrast(&background).abs();
+    Ok(tera::to_value(lc >= apca_min_lc(size, weight))?)
+}
+
+fn apca_colors(args: &HashMap<String, tera::Value>) -> Result<(Color, Color), tera::Error> {
+    let text: Color = tera::from_value(
+        args.get("text")
+            .ok_or_else(|| tera::Error::msg("text is required"))?
+            .clone(),
+    )?;
+    let background: Color = tera::from_value(
+        args.get("background")
+            .ok_or_else(|| tera::Error::msg("background is required"))?
+            .clone(),
+    )?;
+    Ok((text, background))
+}
+
+/// the minimum APCA `Lc` for text of the given size (in px) and weight,
+/// following the APCA bronze simple mode levels.
+fn apca_min_lc(size: f64, weight: u16) -> f64 {
+    let bold = weight >= 700;
+    let min_lc = if size >= 36.0 || (bold && size >= 24.0) {
+        45.0
+    } else if size >= 24.0 || (bold && size >= 16.0) {
+        60.0
+    } else if size >= 18.0 || (bold && size >= 14.0) {
+        75.0
+    } else {
+        90.0
+    };
+    // thin fonts need more contrast than their size suggests
+    if weight < 400 {
+        min_lc + 15.0
+    } else {
+        min_lc
+    }
+}
+
+pub fn color(args: &HashMap<String, tera::Value>) -> Result<tera::Value, tera::Error> {
+    let color = match (args.get("hex"), args.get("rgb"), args.get("hsl")) {
+        (Some(hex), None, None) => {
+            if !hex.is_string() {
+                return Err(tera::Error::msg("hex must be a string"));
+            }
+            Color::from_value(hex)?
+        }
+        (None, Some(value), None) | (None, None, Some(value)) => Color::from_value(value)?,
+        _ => {
+            return Err(tera::Error::msg(
+                "exactly one of hex, rgb or hsl is required",
+            ))
+        }
+    };
+    Ok(tera::to_value(color)?)
+}
+
+pub fn gradient(args: &HashMap<String, tera::Value>) -> Result<tera::Value, tera::Error> {
+    let from: Color = tera::from_value(
+        args.get("from")
+            .ok_or_else(|| tera::Error::msg("from is required"))?
+            .clone(),
+    )?;
+    let to: Color = tera::from_value(
+        args.get("to")
+            .ok_or_else(|| tera::Error::msg("to is required"))?
+            .clone(),
+    )?;
+    let steps: u32 = tera::from_value(
+        args.get("steps")
+            .ok_or_else(|| tera::Error::msg("steps is required"))?
+            .clone(),
+    )?;
+    let space = Interpolation::from_args(args)?;
+    Ok(tera::to_value(Color::gradient(&from, &to, steps, space)?)?)
+}
+
 pub fn read_file_handler(
     template_directory: PathBuf,
 ) -> impl Fn(&HashMap<String, tera::Value>) -> Result<tera::Value, tera::Error> {
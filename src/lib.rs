@@ -1,5 +1,6 @@
 pub mod cli;
 pub mod context;
+pub mod export;
 pub mod filters;
 pub mod frontmatter;
 pub mod functions;
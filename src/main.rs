@@ -7,25 +7,20 @@ use std::{
 };
 
 use anyhow::{anyhow, Context as _};
-use catppuccin::FlavorName;
-use clap::Parser as _;
 use encoding_rs_io::DecodeReaderBytes;
+use indexmap::IndexMap;
 use itertools::Itertools;
 use whiskers::{
-    cli::{Args, OutputFormat},
+    cli::{Args, ColorOverrides, ExportFormat, OutputFormat},
     context::merge_values,
-    frontmatter, markdown,
+    export, frontmatter, markdown,
     matrix::{self, Matrix},
-    models::{self, HEX_FORMAT},
+    models::{self, HexFormat},
     templating,
 };
 
 const FRONTMATTER_OPTIONS_SECTION: &str = "whiskers";
 
-fn default_hex_format() -> String {
-    "{{r}}{{g}}{{b}}{{z}}".to_string()
-}
-
 #[derive(Default, Debug, serde::Deserialize)]
 struct TemplateOptions {
     version: Option<(semver::VersionReq, String)>,
@@ -33,12 +28,13 @@ struct TemplateOptions {
     filename: Option<String>,
     hex_format: String,
     skip_if: Option<String>,
+    computed: IndexMap<String, String>,
 }
 
 impl TemplateOptions {
     fn from_frontmatter(
         frontmatter: &HashMap<String, tera::Value>,
-        only_flavor: Option<FlavorName>,
+        flavors: &[String],
     ) -> anyhow::Result<Self> {
         // a `TemplateOptions` object before matrix transformation
         #[derive(serde::Deserialize)]
@@ -51,6 +47,8 @@ impl TemplateOptions {
             #[serde(default)]
             capitalize_hex: bool,
             skip_if: Option<String>,
+            #[serde(default)]
+            computed: IndexMap<String, String>,
         }
 
         if let Some(opts_section) = frontmatter.get(FRONTMATTER_OPTIONS_SECTION) {
@@ -59,7 +57,7 @@ impl TemplateOptions {
 
             let matrix = raw_opts
                 .matrix
-                .map(|m| matrix::from_values(m, only_flavor))
+                .map(|m| matrix::from_values(m, flavors))
                 .transpose()
                 .context("Frontmatter matrix is invalid")?;
 
@@ -78,7 +76,7 @@ impl TemplateOptions {
                 }
 
                 let prefix = raw_opts.hex_prefix.unwrap_or_default();
-                let components = default_hex_format();
+                let components = HexFormat::DEFAULT;
                 if raw_opts.capitalize_hex {
                     format!("{prefix}{}", components.to_uppercase())
                 } else {
@@ -99,10 +97,11 @@ impl TemplateOptions {
                 filename: raw_opts.filename,
                 hex_format,
                 skip_if: raw_opts.skip_if,
+                computed: raw_opts.computed,
             })
         } else {
             Ok(Self {
-                hex_format: default_hex_format(),
+                hex_format: HexFormat::DEFAULT.to_string(),
                 ..Default::default()
             })
         }
@@ -111,8 +110,15 @@ impl TemplateOptions {
 
 fn main() -> anyhow::Result<()> {
     // parse command-line arguments & template frontmatter
-    let args = Args::parse();
-    handle_list_flags(&args)?;
+    let args = Args::parse_ordered()
+        .with_env_overrides()
+        .context("Environment overrides are invalid")?;
+    let color_overrides = args.merged_color_overrides();
+    let flavors = models::flavor_identifiers(color_overrides.as_ref());
+    if let Err(e) = args.validate_flavor(&flavors) {
+        e.exit();
+    }
+    handle_standalone_flags(&args, color_overrides.as_ref())?;
 
     let template_arg = args
         .template
@@ -123,74 +129,58 @@ fn main() -> anyhow::Result<()> {
     let template_directory =
         template_directory(&template_arg).context("Template file does not exist")?;
 
-    let mut decoder = DecodeReaderBytes::new(
-        template_arg
-            .into_reader()
-            .context("Failed to open template file")?,
-    );
-    let mut template = String::new();
-    decoder
-        .read_to_string(&mut template)
-        .context("Template could not be read")?;
+    let template = read_template(template_arg)?;
 
     let doc = frontmatter::parse(&template).context("Frontmatter is invalid")?;
-    let mut template_opts =
-        TemplateOptions::from_frontmatter(&doc.frontmatter, args.flavor.map(Into::into))
-            .context("Could not get template options from frontmatter")?;
+    let mut frontmatter = frontmatter::resolve_extends(doc.frontmatter, &template_directory)
+        .context("Frontmatter `extends` could not be resolved")?;
+    let mut template_opts = TemplateOptions::from_frontmatter(
+        &frontmatter,
+        &args.flavor.clone().map_or(flavors, |flavor| vec![flavor]),
+    )
+    .context("Could not get template options from frontmatter")?;
 
     if !template_from_stdin && !template_is_compatible(&template_opts) {
         std::process::exit(1);
     }
 
     // merge frontmatter with command-line overrides and add to Tera context
-    let mut frontmatter = doc.frontmatter;
-    if let Some(ref overrides) = args.overrides {
-        for (key, value) in overrides {
-            frontmatter
-                .entry(key.clone())
-                .and_modify(|v| {
-                    *v = merge_values(v, value);
-                })
-                .or_insert(
-                    tera::to_value(value)
-                        .with_context(|| format!("Value of {key} override is invalid"))?,
-                );
-
-            // overrides also work on matrix iterables
-            if let Some(ref mut matrix) = template_opts.matrix {
-                override_matrix(matrix, value, key)?;
-            }
-        }
-    }
+    apply_overrides(
+        &mut frontmatter,
+        &mut template_opts,
+        &args.frontmatter_overrides(),
+    )?;
+    check_extra_colors(color_overrides.as_ref(), &frontmatter, &template_opts)?;
     let mut ctx = tera::Context::new();
     for (key, value) in &frontmatter {
         ctx.insert(key, &value);
     }
 
-    HEX_FORMAT
-        .set(template_opts.hex_format)
-        .expect("can always set HEX_FORMAT");
-
     // build the palette and add it to the templating context
-    let palette = models::build_palette(args.color_overrides.as_ref())
-        .context("Palette context cannot be built")?;
+    let hex_format = HexFormat::parse(&template_opts.hex_format)
+        .context("Frontmatter `hex_format` is invalid")?;
+    let palette =
+        models::build_palette(args.palette.as_ref(), color_overrides.as_ref(), &hex_format)
+            .context("Palette context cannot be built")?;
 
     ctx.insert("flavors", &palette.flavors);
-    if let Some(flavor) = args.flavor {
-        let flavor: catppuccin::FlavorName = flavor.into();
-        let flavor = &palette.flavors[flavor.identifier()];
-        ctx.insert("flavor", flavor);
-
-        // also throw in the flavor's colors for convenience
-        for (_, color) in flavor {
-            ctx.insert(&color.identifier, &color);
-        }
+    let flavor = args.flavor.as_ref().map(|flavor| &palette.flavors[flavor]);
+    if let Some(flavor) = flavor {
+        insert_flavor(&mut ctx, flavor);
     }
 
     // build the Tera engine
-    let mut tera = templating::make_engine(&template_directory);
+    let environment = templating::Environment {
+        template_directory: &template_directory,
+        palette: &palette,
+        hex_format: &hex_format,
+        flavor,
+    };
+    let mut tera = templating::make_engine(&environment);
     tera.add_raw_template(&template_name, &doc.body)
         .context("Template is invalid")?;
+    templating::add_computed(&mut tera, &template_opts.computed)
+        .context("Frontmatter computed values are invalid")?;
 
     if let Some(matrix) = template_opts.matrix {
         let Some(filename_template) = template_opts.filename else {
@@ -201,9 +191,10 @@ fn main() -> anyhow::Result<()> {
             matrix,
             &filename_template,
             template_opts.skip_if.as_deref(),
+            &template_opts.computed,
             &ctx,
-            &palette,
-            &tera,
+            &environment,
+            &mut tera,
             &template_name,
             &args,
         )
@@ -216,6 +207,9 @@ fn main() -> anyhow::Result<()> {
             })
             .transpose()?;
 
+        templating::insert_computed(&tera, &template_opts.computed, &mut ctx)
+            .context("Frontmatter computed values could not be evaluated")?;
+
         render_single_output(
             &ctx,
             &tera,
@@ -231,22 +225,164 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn handle_list_flags(args: &Args) -> anyhow::Result<()> {
+/// read the template, which may be UTF-16 if it starts with a byte order mark.
+fn read_template(template_arg: clap_stdin::FileOrStdin) -> anyhow::Result<String> {
+    let mut decoder = DecodeReaderBytes::new(
+        template_arg
+            .into_reader()
+            .context("Failed to open template file")?,
+    );
+    let mut template = String::new();
+    decoder
+        .read_to_string(&mut template)
+        .context("Template could not be read")?;
+    Ok(template)
+}
+
+/// ensure extra colors don't shadow frontmatter values, computed values or
+/// matrix iterables, as they all share the top level of the context.
+fn check_extra_colors(
+    color_overrides: Option<&ColorOverrides>,
+    frontmatter: &HashMap<String, tera::Value>,
+    template_opts: &TemplateOptions,
+) -> anyhow::Result<()> {
+    let Some(color_overrides) = color_overrides else {
+        return Ok(());
+    };
+
+    for identifier in color_overrides.extra.keys() {
+        let shadowed = if frontmatter.contains_key(identifier) {
+            "frontmatter value"
+        } else if template_opts.computed.contains_key(identifier) {
+            "computed value"
+        } else if template_opts
+            .matrix
+            .as_ref()
+            .is_some_and(|matrix| matrix.contains_key(identifier))
+        {
+            "matrix iterable"
+        } else {
+            continue;
+        };
+        anyhow::bail!("Extra color \"{identifier}\" clashes with the {shadowed} of the same name");
+    }
+
+    Ok(())
+}
+
+fn insert_flavor(ctx: &mut tera::Context, flavor: &models::Flavor) {
+    ctx.insert("flavor", flavor);
+
+    // also throw in the flavor's colors for convenience
+    for (_, color) in flavor {
+        ctx.insert(&color.identifier, &color);
+    }
+}
+
+/// handle flags that do their own thing instead of rendering a template.
+fn handle_standalone_flags(
+    args: &Args,
+    color_overrides: Option<&ColorOverrides>,
+) -> anyhow::Result<()> {
     if args.list_functions {
         list_functions(args.output_format)?;
         exit(0);
     }
 
     if args.list_flavors {
-        list_flavors(args.output_format)?;
+        let palette = standalone_palette(args, color_overrides)?;
+        list_flavors(args.output_format, &palette)?;
         exit(0);
     }
 
     if args.list_accents {
-        list_accents(args.output_format);
+        let palette = standalone_palette(args, color_overrides)?;
+        list_accents(args.output_format, &palette);
         exit(0);
     }
 
+    if args.list_colors {
+        let palette = standalone_palette(args, color_overrides)?;
+        list_colors(args.output_format, &selected_flavors(&palette, args))?;
+        exit(0);
+    }
+
+    if let Some(format) = args.export {
+        export_palette(args, format, color_overrides)?;
+        exit(0);
+    }
+
+    if args.audit_accents {
+        let palette = standalone_palette(args, color_overrides)?;
+        audit_accents(
+            args.output_format,
+            &selected_flavors(&palette, args),
+            args.min_delta_e,
+        )?;
+        exit(0);
+    }
+
+    Ok(())
+}
+
+/// build the palette outside of a template, for flags that inspect it.
+fn standalone_palette(
+    args: &Args,
+    color_overrides: Option<&ColorOverrides>,
+) -> anyhow::Result<models::Palette> {
+    models::build_palette(
+        args.palette.as_ref(),
+        color_overrides,
+        &HexFormat::default(),
+    )
+    .context("Palette context cannot be built")
+}
+
+/// the flavor selected with `--flavor`, or every flavor if none was selected.
+fn selected_flavors<'a>(palette: &'a models::Palette, args: &Args) -> Vec<&'a models::Flavor> {
+    args.flavor.as_ref().map_or_else(
+        || palette.flavors.values().collect(),
+        |flavor| vec![&palette.flavors[flavor]],
+    )
+}
+
+fn export_palette(
+    args: &Args,
+    format: ExportFormat,
+    color_overrides: Option<&ColorOverrides>,
+) -> anyhow::Result<()> {
+    let palette = standalone_palette(args, color_overrides)?;
+    let swatches = export::export(&selected_flavors(&palette, args), format)
+        .context("Palette could not be exported")?;
+    std::io::stdout()
+        .write_all(&swatches)
+        .context("Failed to write export to stdout")?;
+
+    Ok(())
+}
+
+fn apply_overrides(
+    frontmatter: &mut HashMap<String, tera::Value>,
+    template_opts: &mut TemplateOptions,
+    overrides: &HashMap<String, serde_json::Value>,
+) -> anyhow::Result<()> {
+    for (key, value) in overrides {
+        frontmatter
+            .entry(key.clone())
+            .and_modify(|v| {
+                *v = merge_values(v, value);
+            })
+            .or_insert(
+                tera::to_value(value)
+                    .with_context(|| format!("Value of {key} override is invalid"))?,
+            );
+
+        // overrides also work on matrix iterables
+        if let Some(ref mut matrix) = template_opts.matrix {
+            override_matrix(matrix, value, key)?;
+        }
+    }
+
     Ok(())
 }
 
@@ -317,7 +453,7 @@ fn list_functions(format: OutputFormat) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn list_flavors(format: OutputFormat) -> anyhow::Result<()> {
+fn list_flavors(format: OutputFormat, palette: &models::Palette) -> anyhow::Result<()> {
     // we want all the flavor info minus the colors
     #[derive(serde::Serialize)]
     struct FlavorInfo {
@@ -350,12 +486,12 @@ fn list_flavors(format: OutputFormat) -> anyhow::Result<()> {
         }
     }
 
-    let flavors = catppuccin::PALETTE
-        .all_flavors()
-        .into_iter()
+    let flavors = palette
+        .flavors
+        .values()
         .map(|f| FlavorInfo {
-            identifier: f.identifier().to_string(),
-            name: f.name.to_string(),
+            identifier: f.identifier.clone(),
+            name: f.name.clone(),
             emoji: f.emoji,
             order: f.order,
             dark: f.dark,
@@ -389,13 +525,207 @@ fn list_flavors(format: OutputFormat) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn list_accents(format: OutputFormat) {
-    let accents = catppuccin::PALETTE
-        .latte
-        .colors
-        .all_colors()
-        .into_iter()
+/// print every pair of accents that's hard to tell apart with a color vision
+/// deficiency.
+fn audit_accents(
+    format: OutputFormat,
+    flavors: &[&models::Flavor],
+    min_delta_e: f64,
+) -> anyhow::Result<()> {
+    #[derive(serde::Serialize)]
+    struct Finding {
+        deficiency: models::Deficiency,
+        colors: [String; 2],
+        delta_e: f64,
+    }
+
+    impl markdown::TableDisplay for Finding {
+        fn table_headings() -> Box<[String]> {
+            vec![
+                "Deficiency".to_string(),
+                "Colors".to_string(),
+                "ΔE".to_string(),
+            ]
+            .into_boxed_slice()
+        }
+
+        fn table_row(&self) -> Box<[String]> {
+            vec![
+                self.deficiency.to_string(),
+                self.colors.join(", "),
+                format!("{:.2}", self.delta_e),
+            ]
+            .into_boxed_slice()
+        }
+    }
+
+    let findings = flavors
+        .iter()
+        .map(|flavor| {
+            let accents = flavor.colors.values().filter(|c| c.accent).collect_vec();
+            let mut findings = Vec::new();
+            for deficiency in models::Deficiency::ALL {
+                let simulated = accents.iter().map(|c| c.simulate(deficiency)).collect_vec();
+                for (a, b) in simulated.iter().tuple_combinations() {
+                    let delta_e = a.delta_e(b);
+                    if delta_e < min_delta_e {
+                        findings.push(Finding {
+                            deficiency,
+                            colors: [a.identifier.clone(), b.identifier.clone()],
+                            delta_e,
+                        });
+                    }
+                }
+            }
+            (*flavor, findings)
+        })
+        .collect_vec();
+
+    println!(
+        "{}",
+        match format {
+            OutputFormat::Json | OutputFormat::Yaml => {
+                let findings = findings
+                    .iter()
+                    .map(|(flavor, findings)| (&flavor.identifier, findings))
+                    .collect::<IndexMap<_, _>>();
+                if matches!(format, OutputFormat::Json) {
+                    serde_json::to_string_pretty(&findings)
+                        .expect("findings are guaranteed to be valid json")
+                } else {
+                    serde_yaml::to_string(&findings)
+                        .expect("findings are guaranteed to be valid yaml")
+                }
+            }
+            OutputFormat::Plain => findings
+                .iter()
+                .flat_map(|(flavor, findings)| {
+                    findings.iter().map(|f| {
+                        format!(
+                            "{} {} {} {} {:.2}",
+                            flavor.identifier, f.deficiency, f.colors[0], f.colors[1], f.delta_e
+                        )
+                    })
+                })
+                .join("\n"),
+            OutputFormat::Markdown | OutputFormat::MarkdownTable => findings
+                .iter()
+                .map(|(flavor, findings)| markdown::display_as_table(findings, &flavor.name))
+                .collect::<Result<Vec<_>, _>>()?
+                .join("\n\n"),
+        }
+    );
+
+    Ok(())
+}
+
+fn list_colors(format: OutputFormat, flavors: &[&models::Flavor]) -> anyhow::Result<()> {
+    #[derive(serde::Serialize)]
+    struct ColorInfo {
+        identifier: String,
+        name: String,
+        accent: bool,
+        hex: String,
+        rgb: [u8; 3],
+        hsl: (u16, f32, f32),
+    }
+
+    impl markdown::TableDisplay for ColorInfo {
+        fn table_headings() -> Box<[String]> {
+            vec![
+                "Identifier".to_string(),
+                "Name".to_string(),
+                "Accent".to_string(),
+                "Hex".to_string(),
+                "RGB".to_string(),
+                "HSL".to_string(),
+            ]
+            .into_boxed_slice()
+        }
+
+        fn table_row(&self) -> Box<[String]> {
+            let [red, green, blue] = self.rgb;
+            let (hue, saturation, lightness) = self.hsl;
+            vec![
+                self.identifier.clone(),
+                self.name.clone(),
+                self.accent.to_string(),
+                format!("`#{}`", self.hex),
+                format!("`rgb({red}, {green}, {blue})`"),
+                format!(
+                    "`hsl({hue}, {:.0}%, {:.0}%)`",
+                    saturation * 100.0,
+                    lightness * 100.0
+                ),
+            ]
+            .into_boxed_slice()
+        }
+    }
+
+    let colors = flavors
+        .iter()
+        .map(|flavor| {
+            let colors = flavor
+                .colors
+                .values()
+                .map(|c| ColorInfo {
+                    identifier: c.identifier.clone(),
+                    name: c.name.clone(),
+                    accent: c.accent,
+                    hex: c.hex.clone(),
+                    rgb: [c.rgb.r, c.rgb.g, c.rgb.b],
+                    hsl: (c.hsl.h, c.hsl.s, c.hsl.l),
+                })
+                .collect::<Vec<_>>();
+            (flavor, colors)
+        })
+        .collect::<Vec<_>>();
+
+    println!(
+        "{}",
+        match format {
+            // for structured data, we output the full color info objects by flavor
+            OutputFormat::Json | OutputFormat::Yaml => {
+                let colors = colors
+                    .iter()
+                    .map(|(flavor, colors)| (&flavor.identifier, colors))
+                    .collect::<IndexMap<_, _>>();
+                if matches!(format, OutputFormat::Json) {
+                    serde_json::to_string_pretty(&colors)
+                        .expect("colors are guaranteed to be valid json")
+                } else {
+                    serde_yaml::to_string(&colors).expect("colors are guaranteed to be valid yaml")
+                }
+            }
+            // for plain output, we list one color per line
+            OutputFormat::Plain => colors
+                .iter()
+                .flat_map(|(flavor, colors)| {
+                    colors
+                        .iter()
+                        .map(|c| format!("{} {} {}", flavor.identifier, c.identifier, c.hex))
+                })
+                .join("\n"),
+            // and finally for human-readable markdown, we show a table per flavor
+            OutputFormat::Markdown | OutputFormat::MarkdownTable => colors
+                .iter()
+                .map(|(flavor, colors)| markdown::display_as_table(colors, &flavor.name))
+                .collect::<anyhow::Result<Vec<_>>>()?
+                .join("\n\n"),
+        }
+    );
+
+    Ok(())
+}
+
+fn list_accents(format: OutputFormat, palette: &models::Palette) {
+    // custom palettes can mark different accents per flavor, so we list them all
+    let accents = palette
+        .flavors
+        .values()
+        .flat_map(|f| f.colors.values())
         .filter(|c| c.accent)
+        .unique_by(|c| &c.identifier)
         .collect::<Vec<_>>();
 
     println!(
@@ -408,7 +738,7 @@ fn list_accents(format: OutputFormat) {
                     .map(|c| {
                         serde_json::json!({
                             "name": c.name,
-                            "identifier": c.identifier(),
+                            "identifier": c.identifier,
                         })
                     })
                     .collect::<Vec<_>>();
@@ -422,15 +752,12 @@ fn list_accents(format: OutputFormat) {
             }
             // for plain output, we just list the identifiers
             OutputFormat::Plain => {
-                accents
-                    .into_iter()
-                    .map(catppuccin::Color::identifier)
-                    .join("\n")
+                accents.into_iter().map(|c| &c.identifier).join("\n")
             }
             // and finally for human-readable markdown, we list the names
             OutputFormat::Markdown | OutputFormat::MarkdownTable => {
                 markdown::display_as_list(
-                    &accents.into_iter().map(|c| c.name).collect::<Vec<_>>(),
+                    &accents.into_iter().map(|c| &c.name).collect::<Vec<_>>(),
                     "Accents",
                 )
             }
@@ -554,9 +881,10 @@ fn render_multi_output(
     matrix: HashMap<String, Vec<String>>,
     filename_template: &str,
     skip_if: Option<&str>,
+    computed: &IndexMap<String, String>,
     ctx: &tera::Context,
-    palette: &models::Palette,
-    tera: &tera::Tera,
+    environment: &templating::Environment,
+    tera: &mut tera::Tera,
     template_name: &str,
     args: &Args,
 ) -> Result<(), anyhow::Error> {
@@ -574,19 +902,27 @@ fn render_multi_output(
             // `{% set flavor = flavors[flavor] %}`
             // at the top of every template.
             if key == "flavor" {
-                let flavor: catppuccin::FlavorName = value.parse()?;
-                let flavor = &palette.flavors[flavor.identifier()];
-                ctx.insert("flavor", flavor);
-
-                // also throw in the flavor's colors for convenience
-                for (_, color) in flavor {
-                    ctx.insert(&color.identifier, &color);
-                }
+                let flavor = environment
+                    .palette
+                    .flavors
+                    .get(&value)
+                    .with_context(|| format!("Unknown flavor: {value}"))?;
+                insert_flavor(&mut ctx, flavor);
+                templating::register_handlers(
+                    tera,
+                    &templating::Environment {
+                        flavor: Some(flavor),
+                        ..*environment
+                    },
+                );
             } else {
                 ctx.insert(key, &value);
             }
         }
 
+        templating::insert_computed(tera, computed, &mut ctx)
+            .context("Frontmatter computed values could not be evaluated")?;
+
         if should_skip(skip_if, &ctx)? {
             continue;
         }
@@ -1,7 +1,5 @@
 use std::collections::HashMap;
 
-use catppuccin::FlavorName;
-
 pub type Matrix = HashMap<String, Vec<String>>;
 
 #[derive(Debug, thiserror::Error)]
@@ -19,11 +17,9 @@ pub enum Error {
 // matrix in frontmatter is a list of strings or objects.
 // objects must have a single key and an array of strings as the value.
 // string array elements are substituted with the array from `iterables`.
-pub fn from_values(
-    values: Vec<tera::Value>,
-    only_flavor: Option<FlavorName>,
-) -> Result<Matrix, Error> {
-    let iterables = magic_iterables(only_flavor);
+// `flavors` are the identifiers the `flavor` iterable expands to.
+pub fn from_values(values: Vec<tera::Value>, flavors: &[String]) -> Result<Matrix, Error> {
+    let iterables = magic_iterables(flavors);
     values
         .into_iter()
         .map(|v| match v {
@@ -44,22 +40,8 @@ pub fn from_values(
         .collect::<Result<Matrix, Error>>()
 }
 
-fn magic_iterables(only_flavor: Option<FlavorName>) -> HashMap<&'static str, Vec<String>> {
-    HashMap::from([
-        (
-            "flavor",
-            only_flavor.map_or_else(
-                || {
-                    catppuccin::PALETTE
-                        .into_iter()
-                        .map(|flavor| flavor.identifier().to_string())
-                        .collect::<Vec<String>>()
-                },
-                |flavor| vec![flavor.identifier().to_string()],
-            ),
-        ),
-        ("accent", ctp_accents()),
-    ])
+fn magic_iterables(flavors: &[String]) -> HashMap<&'static str, Vec<String>> {
+    HashMap::from([("flavor", flavors.to_vec()), ("accent", ctp_accents())])
 }
 
 fn ctp_accents() -> Vec<String> {
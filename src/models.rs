@@ -1,11 +1,10 @@
-use std::sync::OnceLock;
+use std::sync::LazyLock;
 
 use farver::Color as _;
 use indexmap::IndexMap;
-use serde_json::json;
-use tera::Tera;
+use itertools::Itertools as _;
 
-use crate::cli::ColorOverrides;
+use crate::cli::{ColorOverrides, CustomPalette, DerivedFlavor, FlavorOperation};
 
 // a frankenstein mix of Catppuccin & farver types to get all the
 // functionality we want.
@@ -23,9 +22,18 @@ pub struct Flavor {
     pub dark: bool,
     pub light: bool,
     pub colors: IndexMap<String, Color>,
+    pub ansi: Ansi,
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+/// The 16 ANSI terminal colors of a flavor, following the Catppuccin style
+/// guide. Both maps are keyed by the normal color identifier, e.g. `red`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct Ansi {
+    pub normal: IndexMap<String, Color>,
+    pub bright: IndexMap<String, Color>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct Color {
     pub name: String,
     pub identifier: String,
@@ -35,12 +43,23 @@ pub struct Color {
     pub int24: u32,
     pub uint32: u32,
     pub sint32: i32,
+    pub bgr24: u32,
+    pub abgr32: u32,
     pub rgb: RGB,
+    pub normalized: Normalized,
+    pub rgb16: RGB16,
+    pub premultiplied: RGB,
     pub hsl: HSL,
+    pub hsv: HSV,
+    pub hwb: HWB,
+    pub lab: Lab,
+    pub cmyk: CMYK,
+    pub oklab: OKLab,
+    pub oklch: OKLCH,
     pub opacity: u8,
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct RGB {
     pub r: u8,
     pub g: u8,
@@ -48,111 +67,421 @@ pub struct RGB {
     pub channels: [u8; 3],
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+/// The channels of a color as floats from `0.0` to `1.0`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct Normalized {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub a: f64,
+}
+
+/// The channels of a color as 16-bit integers, from `0` to `65535`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct RGB16 {
+    pub r: u16,
+    pub g: u16,
+    pub b: u16,
+    pub a: u16,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct HSL {
     pub h: u16,
     pub s: f32,
     pub l: f32,
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct HSV {
+    pub h: u16,
+    pub s: f32,
+    pub v: f32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct HWB {
+    pub h: u16,
+    pub w: f32,
+    pub b: f32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct Lab {
+    pub l: f32,
+    pub a: f32,
+    pub b: f32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct CMYK {
+    pub c: f32,
+    pub m: f32,
+    pub y: f32,
+    pub k: f32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct OKLab {
+    pub l: f32,
+    pub a: f32,
+    pub b: f32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct OKLCH {
+    pub l: f32,
+    pub c: f32,
+    pub h: f32,
+}
+
+/// A color vision deficiency that can be simulated with [`Color::simulate`].
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Deficiency {
+    /// no red cones
+    Protanopia,
+    /// no green cones
+    Deuteranopia,
+    /// no blue cones
+    Tritanopia,
+    /// no color vision at all
+    Achromatopsia,
+}
+
+impl Deficiency {
+    pub const ALL: [Self; 4] = [
+        Self::Protanopia,
+        Self::Deuteranopia,
+        Self::Tritanopia,
+        Self::Achromatopsia,
+    ];
+}
+
+impl std::fmt::Display for Deficiency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Protanopia => "protanopia",
+            Self::Deuteranopia => "deuteranopia",
+            Self::Tritanopia => "tritanopia",
+            Self::Achromatopsia => "achromatopsia",
+        })
+    }
+}
+
+/// How a color is blended with the color below it, see [`Color::flatten`].
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BlendMode {
+    /// the color is simply placed on top
+    #[default]
+    Normal,
+    /// darkens, like overlapping slides on a projector
+    Multiply,
+    /// lightens, the inverse of multiply
+    Screen,
+    /// multiplies dark backgrounds and screens light ones, keeping contrast
+    Overlay,
+}
+
+impl BlendMode {
+    /// blend a backdrop and source channel, both from 0 to 1.
+    fn blend(self, backdrop: f32, source: f32) -> f32 {
+        let multiply = |a: f32, b: f32| a * b;
+        let screen = |a: f32, b: f32| a.mul_add(-b, a + b);
+        match self {
+            Self::Normal => source,
+            Self::Multiply => multiply(backdrop, source),
+            Self::Screen => screen(backdrop, source),
+            Self::Overlay => {
+                if backdrop <= 0.5 {
+                    multiply(source, 2.0 * backdrop)
+                } else {
+                    screen(source, 2.0f32.mul_add(backdrop, -1.0))
+                }
+            }
+        }
+    }
+}
+
+/// A color space to mix colors in, see [`Color::mix_in`].
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Interpolation {
+    /// gamma-encoded sRGB, like the `mix` filter
+    Srgb,
+    /// perceptually uniform, without the muddy midpoints of sRGB
+    #[default]
+    Oklab,
+    /// like `Oklab`, but keeping chroma by rotating the hue the short way round
+    Oklch,
+}
+
+impl Interpolation {
+    /// read the optional `space` argument of a filter or function.
+    pub fn from_args(args: &std::collections::HashMap<String, tera::Value>) -> tera::Result<Self> {
+        args.get("space").map_or_else(
+            || Ok(Self::default()),
+            |space| {
+                tera::from_value(space.clone())
+                    .map_err(|_| tera::Error::msg("space must be one of srgb, oklab or oklch"))
+            },
+        )
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
-    #[error("Hex formatting failed: {0}")]
-    HexFormat(#[from] tera::Error),
-    #[error("Failed to parse hex color: {0}")]
-    ParseHex(#[from] std::num::ParseIntError),
-}
-
-// we have many functions that need to know how to format hex colors.
-// they can't know this at build time, as the format may be provided by the template.
-// we have little to no available state in many of these functions to store this information.
-// these possible solutions were evaluated:
-// 1. pass the format string to every function that needs it. this is cumbersome and error-prone.
-// 2. store the format string in the `Colour` struct, thus duplicating it for every color. this is wasteful.
-// 3. store it in a global static, and initialize it when the template frontmatter is read.
-// we opted for the third option, with a convenience macro for accessing it.
-pub static HEX_FORMAT: OnceLock<String> = OnceLock::new();
-macro_rules! format_hex {
-    ($r:expr, $g:expr, $b: expr, $a: expr) => {
-        format_hex(
-            $r,
-            $g,
-            $b,
-            $a,
-            &*HEX_FORMAT.get().expect("HEX_FORMAT was never set"),
-        )
-    };
+    #[error("Invalid hex format {format:?}: {message}")]
+    InvalidHexFormat { format: String, message: String },
+    #[error("Failed to parse color {color:?}: {message}")]
+    ParseColor { color: String, message: String },
+    #[error("Unknown color \"{identifier}\" in \"{section}\" color overrides{hint}")]
+    UnknownColor {
+        identifier: String,
+        section: String,
+        hint: String,
+    },
+    #[error("Extra color \"{identifier}\" already exists, override it in \"all\" instead")]
+    ExtraColorExists { identifier: String },
+    #[error("Extra color \"{identifier}\" is not a valid identifier, use only letters, digits and underscores, and don't start with a digit")]
+    InvalidExtraColor { identifier: String },
+    #[error("Extra color \"{identifier}\" is reserved by the template context")]
+    ReservedExtraColor { identifier: String },
+    #[error("Custom palette is missing flavor \"{identifier}\"")]
+    MissingFlavor { identifier: String },
+    #[error("Custom palette flavor \"{flavor}\" is missing color \"{identifier}\"")]
+    MissingColor { flavor: String, identifier: String },
+    #[error("Custom palette contains unknown flavor \"{identifier}\"")]
+    UnknownFlavor { identifier: String },
+    #[error("Derived flavor \"{identifier}\" already exists")]
+    DerivedFlavorExists { identifier: String },
+    #[error("Derived flavor \"{identifier}\" has unknown parent flavor \"{parent}\"")]
+    UnknownParentFlavor { identifier: String, parent: String },
 }
 
-/// attempt to canonicalize a hex string, using the provided format string.
-fn format_hex(r: u8, g: u8, b: u8, a: u8, hex_format: &str) -> tera::Result<String> {
-    Tera::one_off(
-        hex_format,
-        &tera::Context::from_serialize(json!({
-            "r": format!("{r:02x}"),
-            "g": format!("{g:02x}"),
-            "b": format!("{b:02x}"),
-            "a": format!("{a:02x}"),
-            "z": if a == 0xFF { String::new() } else { format!("{a:02x}") },
-            "R": format!("{r:02X}"),
-            "G": format!("{g:02X}"),
-            "B": format!("{b:02X}"),
-            "A": format!("{a:02X}"),
-            "Z": if a == 0xFF { String::new() } else { format!("{a:02X}") },
-        }))
-        .expect("hardcoded context is always valid"),
-        true,
-    )
+/// The format of the `hex` of colors, e.g. `#{{r}}{{g}}{{b}}` or `0x{{A}}{{R}}{{G}}{{B}}`.
+///
+/// the format may be provided by the template, so colors are built with the
+/// [default](HexFormat::DEFAULT) format and, if the template uses another
+/// format, given that format when they reach the template.
+///
+/// formats are parsed once into a list of tokens, so formatting a color is
+/// just a matter of writing out its channels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HexFormat {
+    tokens: Vec<HexToken>,
 }
 
-/// produce three values from a given rgb value and opacity:
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum HexToken {
+    /// text copied as-is
+    Text(String),
+    /// a channel as a 2-digit hex string: `r`, `g`, `b`, `a` and uppercase
+    Channel { index: usize, uppercase: bool },
+    /// the alpha channel, or nothing if the color is opaque: `z` and `Z`
+    OptionalAlpha { uppercase: bool },
+}
+
+static DEFAULT_HEX_FORMAT: LazyLock<HexFormat> =
+    LazyLock::new(|| HexFormat::parse(HexFormat::DEFAULT).expect("default hex format is valid"));
+
+impl HexFormat {
+    /// lowercase `rrggbb`, with the alpha channel only shown if it's not opaque.
+    pub const DEFAULT: &'static str = "{{r}}{{g}}{{b}}{{z}}";
+
+    /// parse a format where `{{r}}`, `{{g}}`, `{{b}}`, `{{a}}` and `{{z}}`,
+    /// or their uppercase versions, are replaced with the channels of the color.
+    pub fn parse(format: &str) -> Result<Self, Error> {
+        let invalid = |message: String| Error::InvalidHexFormat {
+            format: format.to_string(),
+            message,
+        };
+
+        let mut tokens = vec![];
+        let mut rest = format;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                tokens.push(HexToken::Text(rest[..start].to_string()));
+            }
+            let Some(end) = rest[start..].find("}}") else {
+                return Err(invalid("`{{` is never closed".to_string()));
+            };
+            let name = rest[start + 2..start + end].trim();
+            let uppercase = name.starts_with(char::is_uppercase);
+            let channel = |index| HexToken::Channel { index, uppercase };
+            tokens.push(match name {
+                "r" | "R" => channel(0),
+                "g" | "G" => channel(1),
+                "b" | "B" => channel(2),
+                "a" | "A" => channel(3),
+                "z" | "Z" => HexToken::OptionalAlpha { uppercase },
+                _ => {
+                    return Err(invalid(format!(
+                        "unknown variable `{name}`, expected one of r, g, b, a, z, R, G, B, A or Z"
+                    )))
+                }
+            });
+            rest = &rest[start + end + 2..];
+        }
+        if !rest.is_empty() {
+            tokens.push(HexToken::Text(rest.to_string()));
+        }
+
+        Ok(Self { tokens })
+    }
+
+    /// whether this is the [default](HexFormat::DEFAULT) format, which colors
+    /// are already built with.
+    #[must_use]
+    pub fn is_default(&self) -> bool {
+        *self == *DEFAULT_HEX_FORMAT
+    }
+
+    #[must_use]
+    pub fn format(&self, rgb: &RGB, opacity: u8) -> String {
+        let channels = [rgb.r, rgb.g, rgb.b, opacity];
+        let hex = |value: u8, uppercase: bool| {
+            if uppercase {
+                format!("{value:02X}")
+            } else {
+                format!("{value:02x}")
+            }
+        };
+
+        let mut result = String::new();
+        for token in &self.tokens {
+            match token {
+                HexToken::Text(text) => result.push_str(text),
+                HexToken::Channel { index, uppercase } => {
+                    result.push_str(&hex(channels[*index], *uppercase));
+                }
+                HexToken::OptionalAlpha { uppercase } => {
+                    if opacity != 0xFF {
+                        result.push_str(&hex(opacity, *uppercase));
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Default for HexFormat {
+    fn default() -> Self {
+        DEFAULT_HEX_FORMAT.clone()
+    }
+}
+
+/// produce five values from a given rgb value and opacity:
 /// 1. a 24-bit unsigned integer with the format `0xRRGGBB`
 /// 2. a 32-bit unsigned integer with the format `0xAARRGGBB`
 /// 3. a 32-bit signed integer with the format `0xAARRGGBB`
+/// 4. a 24-bit unsigned integer with the format `0xBBGGRR`
+/// 5. a 32-bit unsigned integer with the format `0xAABBGGRR`
 ///
 /// opacity is optional, and defaults to `0xFF`.
-fn rgb_to_ints(rgb: &RGB, opacity: Option<u8>) -> (u32, u32, i32) {
+fn rgb_to_ints(rgb: &RGB, opacity: Option<u8>) -> (u32, u32, i32, u32, u32) {
     let opacity = opacity.unwrap_or(0xFF);
     let uint24 = u32::from_be_bytes([0x00, rgb.r, rgb.g, rgb.b]);
     let uint32 = u32::from_be_bytes([opacity, rgb.r, rgb.g, rgb.b]);
+    let bgr24 = u32::from_be_bytes([0x00, rgb.b, rgb.g, rgb.r]);
+    let abgr32 = u32::from_be_bytes([opacity, rgb.b, rgb.g, rgb.r]);
     #[allow(clippy::cast_possible_wrap)]
-    (uint24, uint32, uint32 as i32)
+    (uint24, uint32, uint32 as i32, bgr24, abgr32)
 }
 
-fn color_from_hex_override(hex: &str, blueprint: &catppuccin::Color) -> Result<Color, Error> {
-    let i = u32::from_str_radix(hex, 16)?;
-    let rgb = RGB::new(
-        ((i >> 16) & 0xFF) as u8,
-        ((i >> 8) & 0xFF) as u8,
-        (i & 0xFF) as u8,
-    );
+/// produce the channels of a given rgb value and opacity as normalized
+/// floats, as 16-bit integers, and premultiplied by the opacity.
+fn rgb_to_channels(rgb: &RGB, opacity: u8) -> (Normalized, RGB16, RGB) {
+    let normalize = |channel: u8| f64::from(channel) / 255.0;
+    // 0xff maps to 0xffff, and every other value scales evenly between
+    let widen = |channel: u8| u16::from(channel) * 0x101;
+    let premultiply = |channel: u8| (f64::from(channel) * normalize(opacity)).round() as u8;
+    (
+        Normalized {
+            r: normalize(rgb.r),
+            g: normalize(rgb.g),
+            b: normalize(rgb.b),
+            a: normalize(opacity),
+        },
+        RGB16 {
+            r: widen(rgb.r),
+            g: widen(rgb.g),
+            b: widen(rgb.b),
+            a: widen(opacity),
+        },
+        RGB::new(premultiply(rgb.r), premultiply(rgb.g), premultiply(rgb.b)),
+    )
+}
+
+/// A color written in a template, see [`Color::from_value`].
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum ColorValue {
+    Color(Box<Color>),
+    Css(String),
+    Channels([u8; 3]),
+    Rgb { r: u8, g: u8, b: u8 },
+    Hsl { h: f32, s: f32, l: f32 },
+}
+
+/// make a `Color` from a string, which may be in any CSS color syntax.
+/// bare hex strings without a leading `#` are also accepted.
+fn color_from_str(color: &str, blueprint: &Color) -> Result<Color, Error> {
+    let [r, g, b, opacity] = csscolorparser::parse(color)
+        .map_err(|e| Error::ParseColor {
+            color: color.to_string(),
+            message: e.to_string(),
+        })?
+        .to_rgba8();
+    let rgb = RGB::new(r, g, b);
     let hsl = farver::rgb(rgb.r, rgb.g, rgb.b).to_hsl();
-    let hex = format_hex!(rgb.r, rgb.g, rgb.b, 0xFF)?;
-    let (int24, uint32, sint32) = rgb_to_ints(&rgb, None);
+    let (hsv, hwb) = (rgb.to_hsv(), rgb.to_hwb());
+    let (lab, cmyk) = (rgb.to_lab(), rgb.to_cmyk());
+    let (oklab, oklch) = rgb.to_oklab();
+    let hex = rgb_to_hex(&rgb, opacity);
+    let (int24, uint32, sint32, bgr24, abgr32) = rgb_to_ints(&rgb, Some(opacity));
+    let (normalized, rgb16, premultiplied) = rgb_to_channels(&rgb, opacity);
     Ok(Color {
-        name: blueprint.name.to_string(),
-        identifier: blueprint.name.identifier().to_string(),
+        name: blueprint.name.clone(),
+        identifier: blueprint.identifier.clone(),
         order: blueprint.order,
         accent: blueprint.accent,
         hex,
         int24,
         uint32,
         sint32,
+        bgr24,
+        abgr32,
         rgb,
+        normalized,
+        rgb16,
+        premultiplied,
         hsl: HSL {
             h: hsl.h.degrees(),
             s: hsl.s.as_f32(),
             l: hsl.l.as_f32(),
         },
-        opacity: 0xFF,
+        hsv,
+        hwb,
+        lab,
+        cmyk,
+        oklab,
+        oklch,
+        opacity,
     })
 }
 
-fn color_from_catppuccin(color: &catppuccin::Color) -> tera::Result<Color> {
-    let hex = format_hex!(color.rgb.r, color.rgb.g, color.rgb.b, 0xFF)?;
+fn color_from_catppuccin(color: &catppuccin::Color) -> Color {
     let rgb: RGB = color.rgb.into();
-    let (int24, uint32, sint32) = rgb_to_ints(&rgb, None);
-    Ok(Color {
+    let hex = rgb_to_hex(&rgb, 0xFF);
+    let (hsv, hwb) = (rgb.to_hsv(), rgb.to_hwb());
+    let (lab, cmyk) = (rgb.to_lab(), rgb.to_cmyk());
+    let (oklab, oklch) = rgb.to_oklab();
+    let (int24, uint32, sint32, bgr24, abgr32) = rgb_to_ints(&rgb, None);
+    let (normalized, rgb16, premultiplied) = rgb_to_channels(&rgb, 0xFF);
+    Color {
         name: color.name.to_string(),
         identifier: color.name.identifier().to_string(),
         order: color.order,
@@ -161,53 +490,88 @@ fn color_from_catppuccin(color: &catppuccin::Color) -> tera::Result<Color> {
         int24,
         uint32,
         sint32,
+        bgr24,
+        abgr32,
         rgb: RGB::new(color.rgb.r, color.rgb.g, color.rgb.b),
+        normalized,
+        rgb16,
+        premultiplied,
         hsl: HSL {
             h: color.hsl.h.round() as u16,
             s: color.hsl.s as f32,
             l: color.hsl.l as f32,
         },
+        hsv,
+        hwb,
+        lab,
+        cmyk,
+        oklab,
+        oklch,
         opacity: 255,
-    })
+    }
 }
 
-/// Build a [`Palette`] from [`catppuccin::PALETTE`], optionally applying color overrides.
-pub fn build_palette(color_overrides: Option<&ColorOverrides>) -> Result<Palette, Error> {
-    // make a `Color` from a `catppuccin::Color`, taking into account `color_overrides`.
-    // overrides apply in this order:
-    // 1. base color
-    // 2. "all" override
-    // 3. flavor override
-    let make_color =
-        |color: &catppuccin::Color, flavor_name: catppuccin::FlavorName| -> Result<Color, Error> {
-            let flavor_override = color_overrides
-                .map(|co| match flavor_name {
-                    catppuccin::FlavorName::Latte => &co.latte,
-                    catppuccin::FlavorName::Frappe => &co.frappe,
-                    catppuccin::FlavorName::Macchiato => &co.macchiato,
-                    catppuccin::FlavorName::Mocha => &co.mocha,
-                })
-                .and_then(|o| o.get(color.name.identifier()).cloned())
-                .map(|s| color_from_hex_override(&s, color))
-                .transpose()?;
+/// Build a [`Palette`] from [`catppuccin::PALETTE`] or a custom palette,
+/// optionally applying color overrides.
+pub fn build_palette(
+    custom_palette: Option<&CustomPalette>,
+    color_overrides: Option<&ColorOverrides>,
+    hex_format: &HexFormat,
+) -> Result<Palette, Error> {
+    let mut palette = match custom_palette {
+        Some(custom_palette) => palette_from_custom(custom_palette)?,
+        None => palette_from_catppuccin(),
+    };
 
-            let all_override = color_overrides
-                .and_then(|co| co.all.get(color.name.identifier()).cloned())
-                .map(|s| color_from_hex_override(&s, color))
-                .transpose()?;
+    if let Some(color_overrides) = color_overrides {
+        palette = apply_color_overrides(palette, color_overrides)?;
+        add_derived_flavors(&mut palette, &color_overrides.derived)?;
+    }
 
-            let base_color = color_from_catppuccin(color)?;
+    // the ANSI colors are taken from the final colors, so they follow overrides
+    add_ansi_colors(&mut palette, color_overrides.map(|co| &co.derived));
 
-            Ok(flavor_override.or(all_override).unwrap_or(base_color))
-        };
+    if hex_format.is_default() {
+        return Ok(palette);
+    }
+    for flavor in palette.flavors.values_mut() {
+        let ansi = flavor
+            .ansi
+            .normal
+            .values_mut()
+            .chain(flavor.ansi.bright.values_mut());
+        for color in flavor.colors.values_mut().chain(ansi) {
+            *color = color.with_hex_format(hex_format);
+        }
+    }
+    Ok(palette)
+}
 
+/// The identifiers of every flavor [`build_palette`] will produce, including
+/// derived flavors.
+#[must_use]
+pub fn flavor_identifiers(color_overrides: Option<&ColorOverrides>) -> Vec<String> {
+    catppuccin::PALETTE
+        .all_flavors()
+        .iter()
+        .map(|f| f.identifier().to_string())
+        .chain(
+            color_overrides
+                .into_iter()
+                .flat_map(|co| co.derived.keys().cloned()),
+        )
+        .unique()
+        .collect()
+}
+
+fn palette_from_catppuccin() -> Palette {
     let mut flavors = IndexMap::new();
     for flavor in &catppuccin::PALETTE {
         let mut colors = IndexMap::new();
         for color in flavor {
             colors.insert(
                 color.name.identifier().to_string(),
-                make_color(color, flavor.name)?,
+                color_from_catppuccin(color),
             );
         }
         flavors.insert(
@@ -220,12 +584,475 @@ pub fn build_palette(color_overrides: Option<&ColorOverrides>) -> Result<Palette
                 dark: flavor.dark,
                 light: !flavor.dark,
                 colors,
+                ansi: Ansi::default(),
+            },
+        );
+    }
+    Palette { flavors }
+}
+
+/// build a palette from a custom palette file. it must contain every
+/// catppuccin flavor, each with every catppuccin color, so that any template
+/// can render against it. flavors may also contain additional colors, which
+/// come after the built-in ones.
+fn palette_from_custom(custom_palette: &CustomPalette) -> Result<Palette, Error> {
+    if let Some(identifier) = custom_palette.flavors.keys().find(|id| {
+        !catppuccin::PALETTE
+            .all_flavors()
+            .iter()
+            .any(|f| f.identifier() == id.as_str())
+    }) {
+        return Err(Error::UnknownFlavor {
+            identifier: identifier.clone(),
+        });
+    }
+
+    let mut flavors = IndexMap::new();
+    for flavor in &catppuccin::PALETTE {
+        let flavor_identifier = flavor.name.identifier();
+        let custom_flavor = custom_palette
+            .flavors
+            .get(flavor_identifier)
+            .ok_or_else(|| Error::MissingFlavor {
+                identifier: flavor_identifier.to_string(),
+            })?;
+
+        let mut colors = IndexMap::new();
+        for color in flavor {
+            let identifier = color.name.identifier();
+            let custom_color =
+                custom_flavor
+                    .colors
+                    .get(identifier)
+                    .ok_or_else(|| Error::MissingColor {
+                        flavor: flavor_identifier.to_string(),
+                        identifier: identifier.to_string(),
+                    })?;
+            let blueprint = Color {
+                name: custom_color
+                    .name()
+                    .map_or_else(|| color.name.to_string(), ToString::to_string),
+                identifier: identifier.to_string(),
+                order: color.order,
+                accent: custom_color.accent().unwrap_or(color.accent),
+                ..Color::default()
+            };
+            colors.insert(
+                identifier.to_string(),
+                color_from_str(custom_color.value(), &blueprint)?,
+            );
+        }
+
+        for (identifier, custom_color) in &custom_flavor.colors {
+            if colors.contains_key(identifier) {
+                continue;
+            }
+            let blueprint = Color {
+                name: custom_color.name().unwrap_or(identifier).to_string(),
+                identifier: identifier.clone(),
+                order: colors.len() as u32,
+                accent: custom_color.accent().unwrap_or(false),
+                ..Color::default()
+            };
+            colors.insert(
+                identifier.clone(),
+                color_from_str(custom_color.value(), &blueprint)?,
+            );
+        }
+
+        flavors.insert(
+            flavor_identifier.to_string(),
+            Flavor {
+                name: custom_flavor.name.clone(),
+                identifier: flavor_identifier.to_string(),
+                emoji: custom_flavor.emoji,
+                order: flavor.order,
+                dark: custom_flavor.dark,
+                light: !custom_flavor.dark,
+                colors,
+                ansi: Ansi::default(),
             },
         );
     }
     Ok(Palette { flavors })
 }
 
+fn apply_color_overrides(
+    mut palette: Palette,
+    color_overrides: &ColorOverrides,
+) -> Result<Palette, Error> {
+    validate_color_overrides(&palette, color_overrides)?;
+
+    for flavor in palette.flavors.values_mut() {
+        // overrides apply in this order:
+        // 1. base color
+        // 2. "all" override
+        // 3. flavor override
+        let flavor_overrides = match flavor.identifier.as_str() {
+            "latte" => &color_overrides.latte,
+            "frappe" => &color_overrides.frappe,
+            "macchiato" => &color_overrides.macchiato,
+            "mocha" => &color_overrides.mocha,
+            _ => unreachable!("palettes only contain catppuccin flavors"),
+        };
+        let apply_overrides = |base_color: Color| -> Result<Color, Error> {
+            let flavor_override = flavor_overrides
+                .get(&base_color.identifier)
+                .map(|s| color_from_str(s, &base_color))
+                .transpose()?;
+
+            let all_override = color_overrides
+                .all
+                .get(&base_color.identifier)
+                .map(|s| color_from_str(s, &base_color))
+                .transpose()?;
+
+            Ok(flavor_override.or(all_override).unwrap_or(base_color))
+        };
+
+        for color in flavor.colors.values_mut() {
+            *color = apply_overrides(color.clone())?;
+        }
+
+        // extra colors come after the existing ones, in the order they were given
+        for (identifier, value) in &color_overrides.extra {
+            let blueprint = Color {
+                name: identifier.clone(),
+                identifier: identifier.clone(),
+                order: flavor.colors.len() as u32,
+                ..Color::default()
+            };
+            let color = apply_overrides(color_from_str(value, &blueprint)?)?;
+            flavor.colors.insert(identifier.clone(), color);
+        }
+    }
+
+    Ok(palette)
+}
+
+/// ensure every overridden color exists, either in the palette or as an
+/// extra color, and that extra colors don't shadow existing ones.
+fn validate_color_overrides(
+    palette: &Palette,
+    color_overrides: &ColorOverrides,
+) -> Result<(), Error> {
+    let existing = palette
+        .flavors
+        .values()
+        .flat_map(|f| f.colors.keys().map(String::as_str))
+        .unique()
+        .collect::<Vec<_>>();
+
+    for identifier in color_overrides.extra.keys() {
+        let identifier = identifier.clone();
+        if existing.contains(&identifier.as_str()) {
+            return Err(Error::ExtraColorExists { identifier });
+        }
+        // extra colors are top-level context variables, so must be usable as one
+        if !is_tera_identifier(&identifier) {
+            return Err(Error::InvalidExtraColor { identifier });
+        }
+        if RESERVED_IDENTIFIERS.contains(&identifier.as_str()) {
+            return Err(Error::ReservedExtraColor { identifier });
+        }
+    }
+
+    let known = existing
+        .into_iter()
+        .chain(color_overrides.extra.keys().map(String::as_str))
+        .collect::<Vec<_>>();
+
+    for (section, overrides) in [
+        ("all", &color_overrides.all),
+        ("latte", &color_overrides.latte),
+        ("frappe", &color_overrides.frappe),
+        ("macchiato", &color_overrides.macchiato),
+        ("mocha", &color_overrides.mocha),
+    ] {
+        // sort so that the first error is always the same one
+        let unknown = overrides
+            .keys()
+            .filter(|id| !known.contains(&id.as_str()))
+            .min();
+        if let Some(identifier) = unknown {
+            return Err(unknown_color(identifier, section, &known));
+        }
+    }
+
+    Ok(())
+}
+
+/// context variables and tera keywords that extra colors can't be named after.
+const RESERVED_IDENTIFIERS: &[&str] = &[
+    "flavor", "flavors", "true", "false", "True", "False", "and", "or", "not", "in", "is",
+];
+
+/// whether `s` can be used as a variable name in a tera template.
+fn is_tera_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// make an [`Error::UnknownColor`], suggesting the most similar known color.
+fn unknown_color(identifier: &str, section: &str, known: &[&str]) -> Error {
+    let hint = known
+        .iter()
+        .map(|k| (strsim::jaro_winkler(identifier, k), k))
+        .filter(|(similarity, _)| *similarity > 0.7)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, k)| format!(", did you mean \"{k}\"?"))
+        .unwrap_or_default();
+    Error::UnknownColor {
+        identifier: identifier.to_string(),
+        section: section.to_string(),
+        hint,
+    }
+}
+
+/// add each derived flavor to the palette, in the order they were given.
+/// a derived flavor may use any flavor before it as its parent.
+fn add_derived_flavors(
+    palette: &mut Palette,
+    derived: &IndexMap<String, DerivedFlavor>,
+) -> Result<(), Error> {
+    for (identifier, derived_flavor) in derived {
+        if palette.flavors.contains_key(identifier) {
+            return Err(Error::DerivedFlavorExists {
+                identifier: identifier.clone(),
+            });
+        }
+        let parent = palette.flavors.get(&derived_flavor.parent).ok_or_else(|| {
+            Error::UnknownParentFlavor {
+                identifier: identifier.clone(),
+                parent: derived_flavor.parent.clone(),
+            }
+        })?;
+
+        let mut colors = parent.colors.clone();
+        let section = format!("derived.{identifier}");
+        for operation in &derived_flavor.operations {
+            apply_flavor_operation(&mut colors, operation, &section)?;
+        }
+
+        let dark = derived_flavor.dark.unwrap_or(parent.dark);
+        let flavor = Flavor {
+            name: derived_flavor
+                .name
+                .clone()
+                .unwrap_or_else(|| identifier.clone()),
+            identifier: identifier.clone(),
+            emoji: derived_flavor.emoji.unwrap_or(parent.emoji),
+            order: palette.flavors.len() as u32,
+            dark,
+            light: !dark,
+            colors,
+            ansi: Ansi::default(),
+        };
+        palette.flavors.insert(identifier.clone(), flavor);
+    }
+
+    Ok(())
+}
+
+/// fill in the ANSI colors of every flavor from its colors.
+fn add_ansi_colors(palette: &mut Palette, derived: Option<&IndexMap<String, DerivedFlavor>>) {
+    for flavor in palette.flavors.values_mut() {
+        let reference = reference_flavor(&flavor.identifier, derived);
+        flavor.ansi = ansi_colors(reference, &flavor.colors);
+    }
+}
+
+/// the Catppuccin flavor a flavor is based on, following derived flavors back
+/// to their root. custom palettes share the identifiers of Catppuccin flavors.
+fn reference_flavor(
+    identifier: &str,
+    derived: Option<&IndexMap<String, DerivedFlavor>>,
+) -> &'static catppuccin::Flavor {
+    let mut identifier = identifier;
+    loop {
+        if let Some(flavor) = catppuccin::PALETTE
+            .all_flavors()
+            .into_iter()
+            .find(|flavor| flavor.identifier() == identifier)
+        {
+            return flavor;
+        }
+        match derived.and_then(|derived| derived.get(identifier)) {
+            Some(derived_flavor) => identifier = &derived_flavor.parent,
+            None => return &catppuccin::PALETTE.mocha,
+        }
+    }
+}
+
+/// map the ANSI colors of a Catppuccin flavor onto the given colors.
+///
+/// most ANSI colors are palette colors, and so are taken from `colors`. the
+/// bright variants of the other colors are the official ones if their normal
+/// color is unchanged, and are otherwise shifted from it in `OKLCH` by as much
+/// as the official bright color is shifted from the official normal color.
+fn ansi_colors(reference: &catppuccin::Flavor, colors: &IndexMap<String, Color>) -> Ansi {
+    let palette_color = |rgb: catppuccin::Rgb| {
+        reference
+            .colors
+            .iter()
+            .find(|color| color.rgb == rgb)
+            .map(|color| &colors[color.name.identifier()])
+    };
+    let ansi_color = |color: &Color, ansi: &catppuccin::AnsiColor| Color {
+        name: ansi.name.to_string(),
+        identifier: ansi.name.identifier().to_string(),
+        order: u32::from(ansi.code),
+        accent: false,
+        ..color.clone()
+    };
+
+    let mut ansi = Ansi::default();
+    for pair in reference.ansi_colors.all_pairs().all_ansi_color_pairs() {
+        let normal =
+            palette_color(pair.normal.rgb).expect("every normal ANSI color is a palette color");
+        let bright = match palette_color(pair.bright.rgb) {
+            Some(bright) => bright.clone(),
+            None if normal.rgb.channels
+                == [pair.normal.rgb.r, pair.normal.rgb.g, pair.normal.rgb.b] =>
+            {
+                let rgb = RGB::from(pair.bright.rgb);
+                Color::from_rgba(
+                    farver::RGBA {
+                        r: farver::Ratio::from_u8(rgb.r),
+                        g: farver::Ratio::from_u8(rgb.g),
+                        b: farver::Ratio::from_u8(rgb.b),
+                        a: farver::Ratio::from_u8(normal.opacity),
+                    },
+                    normal,
+                )
+            }
+            None => {
+                let (_, official_normal) = RGB::from(pair.normal.rgb).to_oklab();
+                let (_, official_bright) = RGB::from(pair.bright.rgb).to_oklab();
+                let oklch = OKLCH {
+                    l: normal.oklch.l + official_bright.l - official_normal.l,
+                    c: normal.oklch.c + official_bright.c - official_normal.c,
+                    h: normal.oklch.h + official_bright.h - official_normal.h,
+                };
+                Color::from_oklch(&oklch, normal)
+            }
+        };
+
+        let identifier = pair.name.identifier().to_string();
+        ansi.normal
+            .insert(identifier.clone(), ansi_color(normal, &pair.normal));
+        ansi.bright
+            .insert(identifier, ansi_color(&bright, &pair.bright));
+    }
+    ansi
+}
+
+type Adjust<T> = fn(&Color, T) -> Color;
+
+/// the color methods backing an add, sub or mod [`FlavorOperation`].
+struct Adjusters {
+    hue: Adjust<i32>,
+    saturation: Adjust<u8>,
+    lightness: Adjust<u8>,
+    opacity: Adjust<f32>,
+    chroma: Adjust<f32>,
+    perceptual_lightness: Adjust<u8>,
+}
+
+fn apply_flavor_operation(
+    colors: &mut IndexMap<String, Color>,
+    operation: &FlavorOperation,
+    section: &str,
+) -> Result<(), Error> {
+    let known = colors.keys().cloned().collect::<Vec<_>>();
+    let known = known.iter().map(String::as_str).collect::<Vec<_>>();
+
+    let (adjustment, adjusters) = match operation {
+        FlavorOperation::Set(values) => {
+            // sort so that the first error is always the same one
+            for (identifier, value) in values.iter().sorted() {
+                let color = colors
+                    .get_mut(identifier)
+                    .ok_or_else(|| unknown_color(identifier, section, &known))?;
+                *color = color_from_str(value, color)?;
+            }
+            return Ok(());
+        }
+        FlavorOperation::Add(adjustment) => (
+            adjustment,
+            Adjusters {
+                hue: Color::add_hue,
+                saturation: Color::add_saturation,
+                lightness: Color::add_lightness,
+                opacity: Color::add_opacity,
+                chroma: Color::add_chroma,
+                perceptual_lightness: Color::add_perceptual_lightness,
+            },
+        ),
+        FlavorOperation::Sub(adjustment) => (
+            adjustment,
+            Adjusters {
+                hue: Color::sub_hue,
+                saturation: Color::sub_saturation,
+                lightness: Color::sub_lightness,
+                opacity: Color::sub_opacity,
+                chroma: Color::sub_chroma,
+                perceptual_lightness: Color::sub_perceptual_lightness,
+            },
+        ),
+        FlavorOperation::Mod(adjustment) => (
+            adjustment,
+            Adjusters {
+                hue: Color::mod_hue,
+                saturation: Color::mod_saturation,
+                lightness: Color::mod_lightness,
+                opacity: Color::mod_opacity,
+                chroma: Color::mod_chroma,
+                perceptual_lightness: Color::mod_perceptual_lightness,
+            },
+        ),
+    };
+
+    let targets = match adjustment.colors {
+        Some(ref targets) => {
+            if let Some(identifier) = targets.iter().find(|id| !colors.contains_key(*id)) {
+                return Err(unknown_color(identifier, section, &known));
+            }
+            targets.clone()
+        }
+        None => known.iter().map(ToString::to_string).collect(),
+    };
+
+    for identifier in targets {
+        let color = colors
+            .get_mut(&identifier)
+            .expect("targets are guaranteed to exist");
+        if let Some(value) = adjustment.hue {
+            *color = (adjusters.hue)(color, value);
+        }
+        if let Some(value) = adjustment.saturation {
+            *color = (adjusters.saturation)(color, value);
+        }
+        if let Some(value) = adjustment.lightness {
+            *color = (adjusters.lightness)(color, value);
+        }
+        if let Some(value) = adjustment.opacity {
+            *color = (adjusters.opacity)(color, value);
+        }
+        if let Some(value) = adjustment.chroma {
+            *color = (adjusters.chroma)(color, value);
+        }
+        if let Some(value) = adjustment.perceptual_lightness {
+            *color = (adjusters.perceptual_lightness)(color, value);
+        }
+    }
+
+    Ok(())
+}
+
 impl Palette {
     #[must_use]
     pub fn iter(&self) -> indexmap::map::Iter<'_, String, Flavor> {
@@ -258,12 +1085,70 @@ impl<'a> IntoIterator for &'a Flavor {
     }
 }
 
-fn rgb_to_hex(rgb: &RGB, opacity: u8) -> tera::Result<String> {
-    format_hex!(rgb.r, rgb.g, rgb.b, opacity)
+fn rgb_to_hex(rgb: &RGB, opacity: u8) -> String {
+    DEFAULT_HEX_FORMAT.format(rgb, opacity)
 }
 
 impl Color {
-    fn from_hsla(hsla: farver::HSLA, blueprint: &Self) -> tera::Result<Self> {
+    /// the same color, with its `hex` in the given format.
+    #[must_use]
+    pub fn with_hex_format(&self, hex_format: &HexFormat) -> Self {
+        Self {
+            hex: hex_format.format(&self.rgb, self.opacity),
+            ..self.clone()
+        }
+    }
+
+    /// make a color from a value in a template, which may be:
+    ///
+    /// - a color, which is returned as it is.
+    /// - a string in any CSS color syntax, or a bare hex string.
+    /// - RGB channels from 0 to 255, as `[r, g, b]` or `{r, g, b}`.
+    /// - HSL, as `{h, s, l}` with the hue in degrees and the saturation and
+    ///   lightness from 0 to 1, like the `hsl` of colors.
+    ///
+    /// colors made from anything but a color have no name or identifier.
+    pub fn from_value(value: &tera::Value) -> tera::Result<Self> {
+        let value: ColorValue = tera::from_value(value.clone()).map_err(|_| {
+            tera::Error::msg(
+                "not a color: expected a color, a CSS color string, [r, g, b] channels, or an object with r, g and b or h, s and l",
+            )
+        })?;
+        let blueprint = Self::default();
+        let rgba = |r, g, b| farver::RGBA {
+            r: farver::Ratio::from_u8(r),
+            g: farver::Ratio::from_u8(g),
+            b: farver::Ratio::from_u8(b),
+            a: farver::Ratio::from_u8(0xFF),
+        };
+        Ok(match value {
+            ColorValue::Color(color) => *color,
+            ColorValue::Css(color) => {
+                color_from_str(&color, &blueprint).map_err(|e| tera::Error::msg(e.to_string()))?
+            }
+            ColorValue::Channels([r, g, b]) | ColorValue::Rgb { r, g, b } => {
+                Self::from_rgba(rgba(r, g, b), &blueprint)
+            }
+            ColorValue::Hsl { h, s, l } => {
+                if !(0.0..=1.0).contains(&s) || !(0.0..=1.0).contains(&l) {
+                    return Err(tera::Error::msg(format!(
+                        "hsl saturation and lightness must be between 0 and 1, got s={s} and l={l}"
+                    )));
+                }
+                Self::from_hsla(
+                    farver::HSLA {
+                        h: farver::Angle::new(hue_degrees(h)),
+                        s: farver::Ratio::from_f32(s),
+                        l: farver::Ratio::from_f32(l),
+                        a: farver::Ratio::from_u8(0xFF),
+                    },
+                    &blueprint,
+                )
+            }
+        })
+    }
+
+    fn from_hsla(hsla: farver::HSLA, blueprint: &Self) -> Self {
         let rgb = hsla.to_rgb();
         let rgb = RGB::new(rgb.r.as_u8(), rgb.g.as_u8(), rgb.b.as_u8());
         let hsl = HSL {
@@ -271,24 +1156,39 @@ impl Color {
             s: hsla.s.as_f32(),
             l: hsla.l.as_f32(),
         };
+        let (hsv, hwb) = (rgb.to_hsv(), rgb.to_hwb());
+        let (lab, cmyk) = (rgb.to_lab(), rgb.to_cmyk());
+        let (oklab, oklch) = rgb.to_oklab();
         let opacity = hsla.a.as_u8();
-        let (int24, uint32, sint32) = rgb_to_ints(&rgb, Some(opacity));
-        Ok(Self {
+        let (int24, uint32, sint32, bgr24, abgr32) = rgb_to_ints(&rgb, Some(opacity));
+        let (normalized, rgb16, premultiplied) = rgb_to_channels(&rgb, opacity);
+        Self {
             name: blueprint.name.clone(),
             identifier: blueprint.identifier.clone(),
             order: blueprint.order,
             accent: blueprint.accent,
-            hex: rgb_to_hex(&rgb, opacity)?,
+            hex: rgb_to_hex(&rgb, opacity),
             int24,
             uint32,
             sint32,
+            bgr24,
+            abgr32,
             rgb,
+            normalized,
+            rgb16,
+            premultiplied,
             hsl,
+            hsv,
+            hwb,
+            lab,
+            cmyk,
+            oklab,
+            oklch,
             opacity,
-        })
+        }
     }
 
-    fn from_rgba(rgba: farver::RGBA, blueprint: &Self) -> tera::Result<Self> {
+    fn from_rgba(rgba: farver::RGBA, blueprint: &Self) -> Self {
         let hsl = rgba.to_hsl();
         let rgb = RGB::new(rgba.r.as_u8(), rgba.g.as_u8(), rgba.b.as_u8());
         let hsl = HSL {
@@ -296,24 +1196,40 @@ impl Color {
             s: hsl.s.as_f32(),
             l: hsl.l.as_f32(),
         };
+        let (hsv, hwb) = (rgb.to_hsv(), rgb.to_hwb());
+        let (lab, cmyk) = (rgb.to_lab(), rgb.to_cmyk());
+        let (oklab, oklch) = rgb.to_oklab();
         let opacity = rgba.a.as_u8();
-        let (int24, uint32, sint32) = rgb_to_ints(&rgb, Some(opacity));
-        Ok(Self {
+        let (int24, uint32, sint32, bgr24, abgr32) = rgb_to_ints(&rgb, Some(opacity));
+        let (normalized, rgb16, premultiplied) = rgb_to_channels(&rgb, opacity);
+        Self {
             name: blueprint.name.clone(),
             identifier: blueprint.identifier.clone(),
             order: blueprint.order,
             accent: blueprint.accent,
-            hex: rgb_to_hex(&rgb, opacity)?,
+            hex: rgb_to_hex(&rgb, opacity),
             int24,
             uint32,
             sint32,
+            bgr24,
+            abgr32,
             rgb,
+            normalized,
+            rgb16,
+            premultiplied,
             hsl,
+            hsv,
+            hwb,
+            lab,
+            cmyk,
+            oklab,
+            oklch,
             opacity,
-        })
+        }
     }
 
-    pub fn mix(base: &Self, blend: &Self, amount: f64) -> tera::Result<Self> {
+    #[must_use]
+    pub fn mix(base: &Self, blend: &Self, amount: f64) -> Self {
         let amount = (amount * 100.0).clamp(0.0, 100.0).round() as u8;
         let blueprint = base;
         let base: farver::RGBA = base.into();
@@ -323,99 +1239,554 @@ impl Color {
         Self::from_rgba(result, blueprint)
     }
 
-    pub fn mod_hue(&self, hue: i32) -> tera::Result<Self> {
+    /// mix two colors in the given color space, where `amount` is the weight
+    /// of `base` from 0 to 1.
+    #[must_use]
+    pub fn mix_in(base: &Self, blend: &Self, amount: f64, space: Interpolation) -> Self {
+        let amount = amount.clamp(0.0, 1.0);
+        let lerp = |from: f32, to: f32| (amount as f32).mul_add(from - to, to);
+        let opacity = lerp(f32::from(base.opacity), f32::from(blend.opacity)).round() as u8;
+
+        match space {
+            Interpolation::Srgb => Self::mix(base, blend, amount),
+            Interpolation::Oklab => {
+                let [l1, a1, b1, _] = base.to_css_color().to_oklaba();
+                let [l2, a2, b2, _] = blend.to_css_color().to_oklaba();
+                let [r, g, b, _] = csscolorparser::Color::from_oklaba(
+                    lerp(l1, l2),
+                    lerp(a1, a2),
+                    lerp(b1, b2),
+                    1.0,
+                )
+                .to_rgba8();
+                Self::from_rgba(
+                    farver::RGBA {
+                        r: farver::Ratio::from_u8(r),
+                        g: farver::Ratio::from_u8(g),
+                        b: farver::Ratio::from_u8(b),
+                        a: farver::Ratio::from_u8(opacity),
+                    },
+                    base,
+                )
+            }
+            Interpolation::Oklch => {
+                // grays have no hue, so they take on the hue of the other color
+                const ACHROMATIC: f32 = 1e-4;
+                let (from, to) = (&base.oklch, &blend.oklch);
+                let (from_hue, to_hue) = match (from.c < ACHROMATIC, to.c < ACHROMATIC) {
+                    (true, false) => (to.h, to.h),
+                    (false, true) => (from.h, from.h),
+                    _ => (from.h, to.h),
+                };
+                let hue_delta = (from_hue - to_hue + 180.0).rem_euclid(360.0) - 180.0;
+                let oklch = OKLCH {
+                    l: lerp(from.l, to.l),
+                    c: lerp(from.c, to.c),
+                    h: (amount as f32).mul_add(hue_delta, to_hue).rem_euclid(360.0),
+                };
+                Self::from_oklch(
+                    &oklch,
+                    &Self {
+                        opacity,
+                        ..base.clone()
+                    },
+                )
+            }
+        }
+    }
+
+    /// composite the color onto a background with source-over alpha
+    /// compositing, blending the two with the given mode where they overlap.
+    ///
+    /// the result is opaque if the background is, and keeps the name and
+    /// identifier of this color.
+    #[must_use]
+    pub fn flatten(&self, background: &Self, mode: BlendMode) -> Self {
+        let unit = |value: u8| f32::from(value) / 255.0;
+        let (alpha, background_alpha) = (unit(self.opacity), unit(background.opacity));
+        let out_alpha = background_alpha.mul_add(1.0 - alpha, alpha);
+
+        let channel = |source: u8, backdrop: u8| {
+            if out_alpha == 0.0 {
+                return 0;
+            }
+            let (source, backdrop) = (unit(source), unit(backdrop));
+            // the source is only blended where it overlaps the backdrop
+            let blended = (1.0 - background_alpha)
+                .mul_add(source, background_alpha * mode.blend(backdrop, source));
+            let composited = (backdrop * background_alpha).mul_add(1.0 - alpha, blended * alpha);
+            (composited / out_alpha * 255.0).round() as u8
+        };
+        let [r, g, b] =
+            [0, 1, 2].map(|i| channel(self.rgb.channels[i], background.rgb.channels[i]));
+
+        Self::from_rgba(
+            farver::RGBA {
+                r: farver::Ratio::from_u8(r),
+                g: farver::Ratio::from_u8(g),
+                b: farver::Ratio::from_u8(b),
+                a: farver::Ratio::from_u8((out_alpha * 255.0).round() as u8),
+            },
+            self,
+        )
+    }
+
+    /// `steps` colors evenly spaced from `from` to `to`, including both.
+    pub fn gradient(
+        from: &Self,
+        to: &Self,
+        steps: u32,
+        space: Interpolation,
+    ) -> tera::Result<Vec<Self>> {
+        if steps < 2 {
+            return Err(tera::Error::msg("a gradient needs at least 2 steps"));
+        }
+        Ok((0..steps)
+            .map(|step| {
+                Self::mix_in(
+                    from,
+                    to,
+                    1.0 - f64::from(step) / f64::from(steps - 1),
+                    space,
+                )
+            })
+            .collect())
+    }
+
+    /// tints and shades of the color, from lightest to darkest, with the color
+    /// itself in between. the lightest tint and darkest shade are mixed
+    /// `amount` of the way towards white and black respectively.
+    ///
+    /// mixing is done with [`Self::mix_in`], so [`Interpolation::Srgb`] gives
+    /// the same colors as [`Self::mix`].
+    #[must_use]
+    pub fn ramp(&self, tints: u32, shades: u32, amount: f64, space: Interpolation) -> Vec<Self> {
+        let extreme = |value: u8| {
+            Self::from_rgba(
+                farver::RGBA {
+                    r: farver::Ratio::from_u8(value),
+                    g: farver::Ratio::from_u8(value),
+                    b: farver::Ratio::from_u8(value),
+                    a: farver::Ratio::from_u8(self.opacity),
+                },
+                self,
+            )
+        };
+        let (white, black) = (extreme(0xFF), extreme(0x00));
+
+        let tints = (1..=tints).rev().map(|step| {
+            Self::mix_in(
+                self,
+                &white,
+                1.0 - amount * f64::from(step) / f64::from(tints),
+                space,
+            )
+        });
+        let shades = (1..=shades).map(|step| {
+            Self::mix_in(
+                self,
+                &black,
+                1.0 - amount * f64::from(step) / f64::from(shades),
+                space,
+            )
+        });
+        tints
+            .chain(std::iter::once(self.clone()))
+            .chain(shades)
+            .collect()
+    }
+
+    #[must_use]
+    pub fn mod_hue(&self, hue: i32) -> Self {
         let mut hsl: farver::HSL = self.into();
         hsl.h = farver::deg(hue);
         Self::from_hsla(hsl.to_hsla(), self)
     }
 
-    pub fn add_hue(&self, hue: i32) -> tera::Result<Self> {
+    #[must_use]
+    pub fn add_hue(&self, hue: i32) -> Self {
         let hsl: farver::HSL = self.into();
         let hsl = hsl.spin(farver::deg(hue));
         Self::from_hsla(hsl.to_hsla(), self)
     }
 
-    pub fn sub_hue(&self, hue: i32) -> tera::Result<Self> {
+    #[must_use]
+    pub fn sub_hue(&self, hue: i32) -> Self {
         let hsl: farver::HSL = self.into();
         let hsl = hsl.spin(-farver::deg(hue));
         Self::from_hsla(hsl.to_hsla(), self)
     }
 
-    pub fn mod_saturation(&self, saturation: u8) -> tera::Result<Self> {
+    #[must_use]
+    pub fn mod_saturation(&self, saturation: u8) -> Self {
         let mut hsl: farver::HSL = self.into();
         hsl.s = farver::percent(saturation);
         Self::from_hsla(hsl.to_hsla(), self)
     }
 
-    pub fn add_saturation(&self, saturation: u8) -> tera::Result<Self> {
+    #[must_use]
+    pub fn add_saturation(&self, saturation: u8) -> Self {
         let hsl: farver::HSL = self.into();
         let hsl = hsl.saturate(farver::percent(saturation));
         Self::from_hsla(hsl.to_hsla(), self)
     }
 
-    pub fn sub_saturation(&self, saturation: u8) -> tera::Result<Self> {
+    #[must_use]
+    pub fn sub_saturation(&self, saturation: u8) -> Self {
         let hsl: farver::HSL = self.into();
         let hsl = hsl.desaturate(farver::percent(saturation));
         Self::from_hsla(hsl.to_hsla(), self)
     }
 
-    pub fn mod_lightness(&self, lightness: u8) -> tera::Result<Self> {
+    #[must_use]
+    pub fn mod_lightness(&self, lightness: u8) -> Self {
         let mut hsl: farver::HSL = self.into();
         hsl.l = farver::percent(lightness);
         Self::from_hsla(hsl.to_hsla(), self)
     }
 
-    pub fn add_lightness(&self, lightness: u8) -> tera::Result<Self> {
+    #[must_use]
+    pub fn add_lightness(&self, lightness: u8) -> Self {
         let hsl: farver::HSL = self.into();
         let hsl = hsl.lighten(farver::percent(lightness));
         Self::from_hsla(hsl.to_hsla(), self)
     }
 
-    pub fn sub_lightness(&self, lightness: u8) -> tera::Result<Self> {
+    #[must_use]
+    pub fn sub_lightness(&self, lightness: u8) -> Self {
         let hsl: farver::HSL = self.into();
         let hsl = hsl.darken(farver::percent(lightness));
         Self::from_hsla(hsl.to_hsla(), self)
     }
 
-    pub fn mod_opacity(&self, opacity: f32) -> tera::Result<Self> {
-        let opacity = (opacity * 255.0).round() as u8;
-        let (int24, uint32, sint32) = rgb_to_ints(&self.rgb, Some(opacity));
-        Ok(Self {
+    fn to_css_color(&self) -> csscolorparser::Color {
+        csscolorparser::Color::from_rgba8(self.rgb.r, self.rgb.g, self.rgb.b, self.opacity)
+    }
+
+    /// the relative luminance of the color, as defined by WCAG 2.x.
+    ///
+    /// opacity is ignored, as the color behind a translucent color isn't known.
+    #[must_use]
+    pub fn relative_luminance(&self) -> f64 {
+        let [r, g, b] = self.rgb.channels.map(|channel| {
+            let channel = f64::from(channel) / 255.0;
+            if channel <= 0.04045 {
+                channel / 12.92
+            } else {
+                ((channel + 0.055) / 1.055).powf(2.4)
+            }
+        });
+        0.2126f64.mul_add(r, 0.7152f64.mul_add(g, 0.0722 * b))
+    }
+
+    /// the WCAG 2.x contrast ratio between two colors, from 1 to 21.
+    #[must_use]
+    pub fn contrast_ratio(&self, other: &Self) -> f64 {
+        let (a, b) = (self.relative_luminance(), other.relative_luminance());
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    /// the APCA (0.0.98G-4g) lightness contrast `Lc` of this color as text on
+    /// the given background, from about -108 to 106.
+    ///
+    /// positive values are dark text on a light background, negative values
+    /// light text on a dark background.
+    #[must_use]
+    pub fn apca_contrast(&self, background: &Self) -> f64 {
+        const BLACK_THRESHOLD: f64 = 0.022;
+        const BLACK_CLAMP: f64 = 1.414;
+        const SCALE: f64 = 1.14;
+        const OFFSET: f64 = 0.027;
+        const LOW_CLIP: f64 = 0.1;
+
+        // APCA's screen luminance, with a soft clamp for very dark colors
+        let luminance = |color: &Self| {
+            let [r, g, b] = color.rgb.channels.map(|c| (f64::from(c) / 255.0).powf(2.4));
+            let y = 0.212_672_9f64.mul_add(r, 0.715_152_2f64.mul_add(g, 0.072_175 * b));
+            if y < BLACK_THRESHOLD {
+                y + (BLACK_THRESHOLD - y).powf(BLACK_CLAMP)
+            } else {
+                y
+            }
+        };
+        let (text, background) = (luminance(self), luminance(background));
+        if (background - text).abs() < 0.0005 {
+            return 0.0;
+        }
+
+        let contrast = if background > text {
+            let sapc = (background.powf(0.56) - text.powf(0.57)) * SCALE;
+            if sapc < LOW_CLIP {
+                0.0
+            } else {
+                sapc - OFFSET
+            }
+        } else {
+            let sapc = (background.powf(0.65) - text.powf(0.62)) * SCALE;
+            if sapc > -LOW_CLIP {
+                0.0
+            } else {
+                sapc + OFFSET
+            }
+        };
+        contrast * 100.0
+    }
+
+    /// simulate how the color looks with the given color vision deficiency.
+    ///
+    /// dichromacies use the full-severity matrices from Machado et al. (2009),
+    /// applied in linear sRGB.
+    #[must_use]
+    pub fn simulate(&self, deficiency: Deficiency) -> Self {
+        const PROTANOPIA: [[f32; 3]; 3] = [
+            [0.152_286, 1.052_583, -0.204_868],
+            [0.114_503, 0.786_281, 0.099_216],
+            [-0.003_882, -0.048_116, 1.051_998],
+        ];
+        const DEUTERANOPIA: [[f32; 3]; 3] = [
+            [0.367_322, 0.860_646, -0.227_968],
+            [0.280_085, 0.672_501, 0.047_413],
+            [-0.011_820, 0.042_940, 0.968_881],
+        ];
+        const TRITANOPIA: [[f32; 3]; 3] = [
+            [1.255_528, -0.076_749, -0.178_779],
+            [-0.078_411, 0.930_809, 0.147_602],
+            [0.004_733, 0.691_367, 0.303_900],
+        ];
+        // achromats only perceive luminance
+        const ACHROMATOPSIA: [[f32; 3]; 3] = [[0.2126, 0.7152, 0.0722]; 3];
+
+        let matrix = match deficiency {
+            Deficiency::Protanopia => PROTANOPIA,
+            Deficiency::Deuteranopia => DEUTERANOPIA,
+            Deficiency::Tritanopia => TRITANOPIA,
+            Deficiency::Achromatopsia => ACHROMATOPSIA,
+        };
+        let [r, g, b, _] = self.rgb.to_css_color().to_linear_rgba();
+        let [r, g, b] = matrix.map(|row| {
+            row.iter()
+                .zip([r, g, b])
+                .map(|(m, channel)| m * channel)
+                .sum::<f32>()
+                .clamp(0.0, 1.0)
+        });
+
+        let [r, g, b, _] = csscolorparser::Color::from_linear_rgba(r, g, b, 1.0).to_rgba8();
+        Self::from_rgba(
+            farver::RGBA {
+                r: farver::Ratio::from_u8(r),
+                g: farver::Ratio::from_u8(g),
+                b: farver::Ratio::from_u8(b),
+                a: farver::Ratio::from_u8(self.opacity),
+            },
+            self,
+        )
+    }
+
+    /// the CIEDE2000 color difference between two colors, where about `2.3`
+    /// is just noticeable.
+    #[must_use]
+    pub fn delta_e(&self, other: &Self) -> f64 {
+        self.lab.delta_e(&other.lab)
+    }
+
+    /// the index of the perceptually nearest color in the xterm 256-color
+    /// palette.
+    ///
+    /// the 16 basic colors (0-15) are skipped, as terminals let users
+    /// customize them; see [`Color::ansi16`] for those.
+    #[must_use]
+    pub fn xterm256(&self) -> u8 {
+        nearest_index(
+            &self.lab,
+            (16..=255).map(|index| (index, xterm256_rgb(index))),
+        )
+    }
+
+    /// the index of the perceptually nearest of the 16 basic ANSI colors, using
+    /// xterm's default colors.
+    #[must_use]
+    pub fn ansi16(&self) -> u8 {
+        nearest_index(&self.lab, (0..).zip(ANSI16_RGB))
+    }
+
+    /// the alpha component of a CSS color string, empty if fully opaque.
+    fn css_alpha(&self) -> String {
+        if self.opacity == 0xFF {
+            String::new()
+        } else {
+            format!(" / {:.0}%", f32::from(self.opacity) / 255.0 * 100.0)
+        }
+    }
+
+    /// the color as a CSS `hwb()` string.
+    #[must_use]
+    pub fn css_hwb(&self) -> String {
+        format!(
+            "hwb({} {:.0}% {:.0}%{})",
+            self.hwb.h,
+            self.hwb.w * 100.0,
+            self.hwb.b * 100.0,
+            self.css_alpha()
+        )
+    }
+
+    /// the color as a CSS `lab()` string.
+    #[must_use]
+    pub fn css_lab(&self) -> String {
+        format!(
+            "lab({:.2} {:.2} {:.2}{})",
+            self.lab.l,
+            self.lab.a,
+            self.lab.b,
+            self.css_alpha()
+        )
+    }
+
+    /// the color as a CSS `oklab()` string.
+    #[must_use]
+    pub fn css_oklab(&self) -> String {
+        self.to_css_color().to_css_oklab()
+    }
+
+    /// the color as a CSS `oklch()` string.
+    #[must_use]
+    pub fn css_oklch(&self) -> String {
+        self.to_css_color().to_css_oklch()
+    }
+
+    /// convert an `OKLCH` color back to sRGB, reducing its chroma until it fits
+    /// in the sRGB gamut so that lightness and hue are preserved.
+    fn from_oklch(oklch: &OKLCH, blueprint: &Self) -> Self {
+        const EPSILON: f32 = 1e-4;
+        let lightness = oklch.l.clamp(0.0, 1.0);
+        let to_srgb = |chroma: f32| {
+            csscolorparser::Color::from_oklcha(lightness, chroma, oklch.h.to_radians(), 1.0)
+        };
+        let in_gamut = |color: &csscolorparser::Color| {
+            [color.r, color.g, color.b]
+                .iter()
+                .all(|channel| (-EPSILON..=1.0 + EPSILON).contains(channel))
+        };
+
+        let chroma = oklch.c.max(0.0);
+        let mut color = to_srgb(chroma);
+        if !in_gamut(&color) {
+            let (mut low, mut high) = (0.0, chroma);
+            for _ in 0..16 {
+                let mid = f32::midpoint(low, high);
+                if in_gamut(&to_srgb(mid)) {
+                    low = mid;
+                } else {
+                    high = mid;
+                }
+            }
+            color = to_srgb(low);
+        }
+
+        let [r, g, b, _] = color.to_rgba8();
+        Self::from_rgba(
+            farver::RGBA {
+                r: farver::Ratio::from_u8(r),
+                g: farver::Ratio::from_u8(g),
+                b: farver::Ratio::from_u8(b),
+                a: farver::Ratio::from_u8(blueprint.opacity),
+            },
+            blueprint,
+        )
+    }
+
+    #[must_use]
+    pub fn mod_chroma(&self, chroma: f32) -> Self {
+        let oklch = OKLCH {
+            c: chroma,
+            ..self.oklch.clone()
+        };
+        Self::from_oklch(&oklch, self)
+    }
+
+    #[must_use]
+    pub fn add_chroma(&self, chroma: f32) -> Self {
+        let oklch = OKLCH {
+            c: self.oklch.c + chroma,
+            ..self.oklch.clone()
+        };
+        Self::from_oklch(&oklch, self)
+    }
+
+    #[must_use]
+    pub fn sub_chroma(&self, chroma: f32) -> Self {
+        let oklch = OKLCH {
+            c: self.oklch.c - chroma,
+            ..self.oklch.clone()
+        };
+        Self::from_oklch(&oklch, self)
+    }
+
+    #[must_use]
+    pub fn mod_perceptual_lightness(&self, lightness: u8) -> Self {
+        let oklch = OKLCH {
+            l: f32::from(lightness) / 100.0,
+            ..self.oklch.clone()
+        };
+        Self::from_oklch(&oklch, self)
+    }
+
+    #[must_use]
+    pub fn add_perceptual_lightness(&self, lightness: u8) -> Self {
+        let oklch = OKLCH {
+            l: self.oklch.l + f32::from(lightness) / 100.0,
+            ..self.oklch.clone()
+        };
+        Self::from_oklch(&oklch, self)
+    }
+
+    #[must_use]
+    pub fn sub_perceptual_lightness(&self, lightness: u8) -> Self {
+        let oklch = OKLCH {
+            l: self.oklch.l - f32::from(lightness) / 100.0,
+            ..self.oklch.clone()
+        };
+        Self::from_oklch(&oklch, self)
+    }
+
+    /// the same color, with the given opacity.
+    fn with_opacity(&self, opacity: u8) -> Self {
+        let (int24, uint32, sint32, bgr24, abgr32) = rgb_to_ints(&self.rgb, Some(opacity));
+        let (normalized, rgb16, premultiplied) = rgb_to_channels(&self.rgb, opacity);
+        Self {
             opacity,
-            hex: rgb_to_hex(&self.rgb, opacity)?,
+            hex: rgb_to_hex(&self.rgb, opacity),
             int24,
             uint32,
             sint32,
+            bgr24,
+            abgr32,
+            normalized,
+            rgb16,
+            premultiplied,
             ..self.clone()
-        })
+        }
     }
 
-    pub fn add_opacity(&self, opacity: f32) -> tera::Result<Self> {
+    #[must_use]
+    pub fn mod_opacity(&self, opacity: f32) -> Self {
+        let opacity = (opacity * 255.0).round() as u8;
+        self.with_opacity(opacity)
+    }
+
+    #[must_use]
+    pub fn add_opacity(&self, opacity: f32) -> Self {
         let opacity = (opacity * 255.0).round() as u8;
         let opacity = self.opacity.saturating_add(opacity);
-        let (int24, uint32, sint32) = rgb_to_ints(&self.rgb, Some(opacity));
-        Ok(Self {
-            opacity,
-            hex: rgb_to_hex(&self.rgb, opacity)?,
-            int24,
-            uint32,
-            sint32,
-            ..self.clone()
-        })
+        self.with_opacity(opacity)
     }
 
-    pub fn sub_opacity(&self, opacity: f32) -> tera::Result<Self> {
+    #[must_use]
+    pub fn sub_opacity(&self, opacity: f32) -> Self {
         let opacity = (opacity * 255.0).round() as u8;
         let opacity = self.opacity.saturating_sub(opacity);
-        let (int24, uint32, sint32) = rgb_to_ints(&self.rgb, Some(opacity));
-        Ok(Self {
-            opacity,
-            hex: rgb_to_hex(&self.rgb, opacity)?,
-            int24,
-            uint32,
-            sint32,
-            ..self.clone()
-        })
+        self.with_opacity(opacity)
     }
 }
 
@@ -462,7 +1833,7 @@ impl From<&Color> for farver::HSLA {
 }
 
 impl RGB {
-    const fn new(r: u8, g: u8, b: u8) -> Self {
+    pub(crate) const fn new(r: u8, g: u8, b: u8) -> Self {
         Self {
             r,
             g,
@@ -470,6 +1841,233 @@ impl RGB {
             channels: [r, g, b],
         }
     }
+
+    fn to_css_color(&self) -> csscolorparser::Color {
+        csscolorparser::Color::from_rgba8(self.r, self.g, self.b, 0xFF)
+    }
+
+    /// the color in the `OKLab` and `OKLCH` color spaces, with the hue in degrees.
+    fn to_oklab(&self) -> (OKLab, OKLCH) {
+        let color = self.to_css_color();
+        let [l, a, b, _] = color.to_oklaba();
+        let [_, chroma, hue, _] = color.to_oklcha();
+        (
+            OKLab { l, a, b },
+            OKLCH {
+                l,
+                c: chroma,
+                h: hue.to_degrees().rem_euclid(360.0),
+            },
+        )
+    }
+
+    fn to_hsv(&self) -> HSV {
+        let [h, s, v, _] = self.to_css_color().to_hsva();
+        HSV {
+            h: hue_degrees(h),
+            s,
+            v,
+        }
+    }
+
+    fn to_hwb(&self) -> HWB {
+        let [h, w, b, _] = self.to_css_color().to_hwba();
+        HWB {
+            h: hue_degrees(h),
+            w,
+            b,
+        }
+    }
+
+    /// the color in CIELAB, using the D50 white point like CSS `lab()`.
+    fn to_lab(&self) -> Lab {
+        // linear sRGB to XYZ (D65), then Bradford-adapted to D50, as given in
+        // the CSS Color 4 sample code.
+        const SRGB_TO_XYZ_D50: [[f64; 3]; 3] = [
+            [0.436_065_742, 0.385_151_503, 0.143_078_411],
+            [0.222_493_173, 0.716_887_010, 0.060_619_802],
+            [0.013_923_920, 0.097_081_321, 0.714_099_349],
+        ];
+        const D50_WHITE: [f64; 3] = [0.964_295_676, 1.0, 0.825_104_603];
+        const EPSILON: f64 = 216.0 / 24389.0;
+        const KAPPA: f64 = 24389.0 / 27.0;
+
+        let linear = self.to_css_color().to_linear_rgba().map(f64::from);
+        let [fx, fy, fz] = std::array::from_fn(|i| {
+            let value = SRGB_TO_XYZ_D50[i]
+                .iter()
+                .zip(linear)
+                .map(|(m, channel)| m * channel)
+                .sum::<f64>()
+                / D50_WHITE[i];
+            if value > EPSILON {
+                value.cbrt()
+            } else {
+                KAPPA.mul_add(value, 16.0) / 116.0
+            }
+        });
+
+        Lab {
+            l: 116.0f64.mul_add(fy, -16.0) as f32,
+            a: (500.0 * (fx - fy)) as f32,
+            b: (200.0 * (fy - fz)) as f32,
+        }
+    }
+
+    fn to_cmyk(&self) -> CMYK {
+        let [r, g, b] = [self.r, self.g, self.b].map(|c| f32::from(c) / 255.0);
+        let k = 1.0 - r.max(g).max(b);
+        if k >= 1.0 {
+            return CMYK {
+                c: 0.0,
+                m: 0.0,
+                y: 0.0,
+                k: 1.0,
+            };
+        }
+        CMYK {
+            c: (1.0 - r - k) / (1.0 - k),
+            m: (1.0 - g - k) / (1.0 - k),
+            y: (1.0 - b - k) / (1.0 - k),
+            k,
+        }
+    }
+}
+
+/// xterm's default colors for the 16 basic ANSI colors.
+const ANSI16_RGB: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00],
+    [0xcd, 0x00, 0x00],
+    [0x00, 0xcd, 0x00],
+    [0xcd, 0xcd, 0x00],
+    [0x00, 0x00, 0xee],
+    [0xcd, 0x00, 0xcd],
+    [0x00, 0xcd, 0xcd],
+    [0xe5, 0xe5, 0xe5],
+    [0x7f, 0x7f, 0x7f],
+    [0xff, 0x00, 0x00],
+    [0x00, 0xff, 0x00],
+    [0xff, 0xff, 0x00],
+    [0x5c, 0x5c, 0xff],
+    [0xff, 0x00, 0xff],
+    [0x00, 0xff, 0xff],
+    [0xff, 0xff, 0xff],
+];
+
+/// the color of an index in the xterm 256-color palette.
+fn xterm256_rgb(index: u8) -> [u8; 3] {
+    // the 6x6x6 color cube doesn't use evenly spaced levels
+    const CUBE_LEVELS: [u8; 6] = [0x00, 0x5f, 0x87, 0xaf, 0xd7, 0xff];
+    match index {
+        0..=15 => ANSI16_RGB[usize::from(index)],
+        16..=231 => {
+            let cube = usize::from(index - 16);
+            [cube / 36, cube / 6 % 6, cube % 6].map(|level| CUBE_LEVELS[level])
+        }
+        232..=255 => [8 + (index - 232) * 10; 3],
+    }
+}
+
+/// the index of the candidate nearest to a color by CIEDE2000 distance.
+fn nearest_index(lab: &Lab, candidates: impl Iterator<Item = (u8, [u8; 3])>) -> u8 {
+    candidates
+        .map(|(index, [r, g, b])| (index, lab.delta_e(&RGB::new(r, g, b).to_lab())))
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map_or(0, |(index, _)| index)
+}
+
+impl Lab {
+    /// the CIEDE2000 color difference between two colors, where about `2.3`
+    /// is just noticeable.
+    #[must_use]
+    pub fn delta_e(&self, other: &Self) -> f64 {
+        let [l1, a1, b1] = [self.l, self.a, self.b].map(f64::from);
+        let [l2, a2, b2] = [other.l, other.a, other.b].map(f64::from);
+
+        // how much chroma influences the result, from 0 to 1
+        let chroma_weight =
+            |chroma: f64| (chroma.powi(7) / (chroma.powi(7) + 25f64.powi(7))).sqrt();
+
+        let c_mean = f64::midpoint(a1.hypot(b1), a2.hypot(b2));
+        let a_scale = 0.5f64.mul_add(1.0 - chroma_weight(c_mean), 1.0);
+        let (a1, a2) = (a1 * a_scale, a2 * a_scale);
+        let (c1, c2) = (a1.hypot(b1), a2.hypot(b2));
+        let hue = |a: f64, b: f64| {
+            if a == 0.0 && b == 0.0 {
+                0.0
+            } else {
+                b.atan2(a).to_degrees().rem_euclid(360.0)
+            }
+        };
+        let (h1, h2) = (hue(a1, b1), hue(a2, b2));
+
+        let delta_l = l2 - l1;
+        let delta_c = c2 - c1;
+        let delta_h = if c1 * c2 == 0.0 {
+            0.0
+        } else if (h2 - h1).abs() <= 180.0 {
+            h2 - h1
+        } else if h2 <= h1 {
+            h2 - h1 + 360.0
+        } else {
+            h2 - h1 - 360.0
+        };
+        let delta_h = 2.0 * (c1 * c2).sqrt() * (delta_h / 2.0).to_radians().sin();
+
+        let l_mean = f64::midpoint(l1, l2);
+        let c_mean = f64::midpoint(c1, c2);
+        let h_mean = if c1 * c2 == 0.0 {
+            h1 + h2
+        } else if (h1 - h2).abs() <= 180.0 {
+            f64::midpoint(h1, h2)
+        } else if h1 + h2 < 360.0 {
+            (h1 + h2 + 360.0) / 2.0
+        } else {
+            (h1 + h2 - 360.0) / 2.0
+        };
+
+        // the (weight, multiple, offset) of each cosine term weighting hues
+        let hue_terms = [
+            (-0.17, 1.0, -30.0),
+            (0.24, 2.0, 0.0),
+            (0.32, 3.0, 6.0),
+            (-0.20, 4.0, -63.0),
+        ];
+        let hue_weighting = 1.0
+            + hue_terms
+                .iter()
+                .map(|(weight, multiple, offset)| {
+                    weight * f64::mul_add(*multiple, h_mean, *offset).to_radians().cos()
+                })
+                .sum::<f64>();
+        let l_offset = (l_mean - 50.0).powi(2);
+        let s_l = 1.0 + 0.015 * l_offset / (20.0 + l_offset).sqrt();
+        let s_c = 0.045f64.mul_add(c_mean, 1.0);
+        let s_h = (0.015 * c_mean).mul_add(hue_weighting, 1.0);
+        let rotation = -2.0
+            * chroma_weight(c_mean)
+            * (60.0 * (-((h_mean - 275.0) / 25.0).powi(2)).exp())
+                .to_radians()
+                .sin();
+
+        let (lightness, chroma) = (delta_l / s_l, delta_c / s_c);
+        let hue = delta_h / s_h;
+        (rotation * chroma)
+            .mul_add(
+                hue,
+                lightness.mul_add(lightness, chroma.mul_add(chroma, hue * hue)),
+            )
+            .sqrt()
+    }
+}
+
+/// round a hue to whole degrees, treating the undefined hue of grays as zero.
+fn hue_degrees(hue: f32) -> u16 {
+    if hue.is_nan() {
+        0
+    } else {
+        hue.round().rem_euclid(360.0) as u16
+    }
 }
 
 impl From<catppuccin::Rgb> for RGB {
@@ -477,3 +2075,75 @@ impl From<catppuccin::Rgb> for RGB {
         Self::new(rgb.r, rgb.g, rgb.b)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lab(l: f32, a: f32, b: f32) -> Color {
+        Color {
+            lab: Lab { l, a, b },
+            ..Color::default()
+        }
+    }
+
+    #[test]
+    fn test_hex_format() {
+        let rgb = RGB::new(0xd2, 0x0f, 0x39);
+        let format = |format: &str, opacity| {
+            HexFormat::parse(format)
+                .expect("format is valid")
+                .format(&rgb, opacity)
+        };
+        assert_eq!(format(HexFormat::DEFAULT, 0xFF), "d20f39");
+        assert_eq!(format(HexFormat::DEFAULT, 0x80), "d20f3980");
+        assert_eq!(format("0x{{A}}{{R}}{{G}}{{B}}", 0xFF), "0xFFD20F39");
+        assert_eq!(format("#{{ r }}{{ g }}{{ b }}{{ Z }}", 0x80), "#d20f3980");
+        assert_eq!(format("{{b}}/{{g}}/{{r}}", 0x80), "39/0f/d2");
+        assert_eq!(format("no channels", 0xFF), "no channels");
+
+        assert!(HexFormat::parse("{{x}}").is_err());
+        assert!(HexFormat::parse("#{{r}}{{g").is_err());
+    }
+
+    #[test]
+    fn test_palettes_with_different_hex_formats() {
+        let default = build_palette(None, None, &HexFormat::default()).expect("palette is valid");
+        let custom = build_palette(
+            None,
+            None,
+            &HexFormat::parse("#{{R}}{{G}}{{B}}").expect("format is valid"),
+        )
+        .expect("palette is valid");
+        assert_eq!(default.flavors["mocha"].colors["red"].hex, "f38ba8");
+        assert_eq!(custom.flavors["mocha"].colors["red"].hex, "#F38BA8");
+        assert_eq!(custom.flavors["mocha"].ansi.bright["red"].hex, "#F37799");
+    }
+
+    #[test]
+    fn test_delta_e() {
+        // pairs from Sharma, Wu & Dalal's CIEDE2000 test data
+        let pairs = [
+            ((50.0, 2.6772, -79.7751), (50.0, 0.0, -82.7485), 2.0425),
+            ((50.0, 2.5, 0.0), (73.0, 25.0, -18.0), 27.1492),
+            ((50.0, 2.5, 0.0), (50.0, 0.0, -2.5), 4.3065),
+            (
+                (60.2574, -34.0099, 36.2677),
+                (60.4626, -34.1751, 39.4387),
+                1.2644,
+            ),
+            (
+                (22.7233, 20.0904, -46.694),
+                (23.0331, 14.973, -42.5619),
+                2.0373,
+            ),
+        ];
+        for ((l1, a1, b1), (l2, a2, b2), expected) in pairs {
+            let delta_e = lab(l1, a1, b1).delta_e(&lab(l2, a2, b2));
+            assert!(
+                (delta_e - expected).abs() < 1e-3,
+                "expected {expected}, got {delta_e}"
+            );
+        }
+    }
+}
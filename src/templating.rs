@@ -1,13 +1,16 @@
-use std::path::Path;
+use std::{collections::HashMap, path::Path};
 
 use indexmap::IndexMap;
 use itertools::Itertools as _;
 
-use crate::{filters, functions, markdown};
+use crate::{
+    filters, functions, markdown,
+    models::{Flavor, HexFormat, Palette, RGB},
+};
 
-/// Allows creation of a [`FilterExample`] with the following syntax:
+/// Allows creation of a [`FunctionExample`] with the following syntax:
 ///
-/// `function_example!(mix(base=base, blend=red, amount=0.5) => "#804040")`
+/// `function_example!(gradient(from=red, to=blue, steps=3) => "[#d20f39, #8f5c9d, #1e66f5]")`
 macro_rules! function_example {
     ($name:ident($($key:ident = $value:tt),*) => $output:expr) => {
         $crate::templating::FunctionExample {
@@ -23,7 +26,7 @@ macro_rules! function_example {
 
 /// Allows creation of a [`FilterExample`] with the following syntax:
 ///
-/// `filter_example!(red | add(hue=30)) => "#ff6666")`
+/// `filter_example!(red | add(hue=30) => "#ff6666")`
 macro_rules! filter_example {
     ($value:tt | $name:ident => $output:expr) => {
         $crate::templating::FilterExample {
@@ -45,37 +48,251 @@ macro_rules! filter_example {
     };
 }
 
-pub fn make_engine(template_directory: &Path) -> tera::Tera {
+/// The state of a render that filters and functions can depend on.
+#[derive(Clone, Copy)]
+pub struct Environment<'a> {
+    pub template_directory: &'a Path,
+    pub palette: &'a Palette,
+    pub hex_format: &'a HexFormat,
+    /// the flavor being rendered, if any.
+    pub flavor: Option<&'a Flavor>,
+}
+
+type FilterFn = fn(&tera::Value, &HashMap<String, tera::Value>) -> tera::Result<tera::Value>;
+type FunctionFn = fn(&HashMap<String, tera::Value>) -> tera::Result<tera::Value>;
+
+/// How a [`Filter`] is implemented.
+pub enum FilterImpl {
+    /// a filter that doesn't produce colors.
+    Plain(FilterFn),
+    /// a filter that produces a color or a list of colors, which are given
+    /// the template's hex format.
+    Color(FilterFn),
+    /// a filter built from the state of the render.
+    Handler(fn(&Environment) -> Box<dyn tera::Filter>),
+}
+
+/// How a [`Function`] is implemented.
+pub enum FunctionImpl {
+    /// a function that doesn't produce colors.
+    Plain(FunctionFn),
+    /// a function that produces a color or a list of colors, which are given
+    /// the template's hex format.
+    Color(FunctionFn),
+    /// a function built from the state of the render.
+    Handler(fn(&Environment) -> Box<dyn tera::Function>),
+}
+
+/// Make a Tera engine with every filter in [`all_filters`] and every function
+/// in [`all_functions`] registered.
+#[must_use]
+pub fn make_engine(environment: &Environment) -> tera::Tera {
+    let hex_format = environment.hex_format;
     let mut tera = tera::Tera::default();
-    tera.register_filter("add", filters::add);
-    tera.register_filter("sub", filters::sub);
-    tera.register_filter("mod", filters::modify);
-    tera.register_filter("urlencode_lzma", filters::urlencode_lzma);
-    tera.register_filter("trunc", filters::trunc);
-    tera.register_filter("mix", filters::mix);
-    tera.register_filter("hex", filters::hex);
-    tera.register_filter("css_rgb", filters::css_rgb);
-    tera.register_filter("css_rgba", filters::css_rgba);
-    tera.register_filter("css_hsl", filters::css_hsl);
-    tera.register_filter("css_hsla", filters::css_hsla);
-    tera.register_function("if", functions::if_fn);
-    tera.register_function("object", functions::object);
-    tera.register_function("css_rgb", functions::css_rgb);
-    tera.register_function("css_rgba", functions::css_rgba);
-    tera.register_function("css_hsl", functions::css_hsl);
-    tera.register_function("css_hsla", functions::css_hsla);
-    tera.register_function(
-        "read_file",
-        functions::read_file_handler(template_directory.to_owned()),
-    );
+    for Filter {
+        name,
+        implementation,
+        arguments,
+        ..
+    } in all_filters()
+    {
+        match implementation {
+            FilterImpl::Plain(implementation) => {
+                tera.register_filter(&name, checked_filter(arguments, implementation));
+            }
+            FilterImpl::Color(implementation) if hex_format.is_default() => {
+                tera.register_filter(&name, checked_filter(arguments, implementation));
+            }
+            FilterImpl::Color(implementation) => tera.register_filter(
+                &name,
+                checked_filter(
+                    arguments,
+                    with_hex_format(implementation, hex_format.clone()),
+                ),
+            ),
+            FilterImpl::Handler(_) => {}
+        }
+    }
+    for Function {
+        name,
+        implementation,
+        arguments,
+        ..
+    } in all_functions()
+    {
+        match implementation {
+            FunctionImpl::Plain(implementation) => {
+                tera.register_function(&name, checked_function(arguments, implementation));
+            }
+            FunctionImpl::Color(implementation) if hex_format.is_default() => {
+                tera.register_function(&name, checked_function(arguments, implementation));
+            }
+            FunctionImpl::Color(implementation) => tera.register_function(
+                &name,
+                checked_function(
+                    arguments,
+                    color_function(implementation, hex_format.clone()),
+                ),
+            ),
+            FunctionImpl::Handler(_) => {}
+        }
+    }
+    register_handlers(&mut tera, environment);
     tera
 }
 
+/// Register the filters and functions built from the state of the render,
+/// replacing any that were built from a previous state.
+///
+/// This must be called whenever the environment changes, such as when a
+/// matrix render moves on to the next flavor.
+pub fn register_handlers(tera: &mut tera::Tera, environment: &Environment) {
+    for filter in all_filters() {
+        if let FilterImpl::Handler(make) = filter.implementation {
+            let implementation = make(environment);
+            tera.register_filter(
+                &filter.name,
+                checked_filter(
+                    filter.arguments,
+                    move |value: &tera::Value, args: &HashMap<String, tera::Value>| {
+                        implementation.filter(value, args)
+                    },
+                ),
+            );
+        }
+    }
+    for function in all_functions() {
+        if let FunctionImpl::Handler(make) = function.implementation {
+            let implementation = make(environment);
+            tera.register_function(
+                &function.name,
+                checked_function(
+                    function.arguments,
+                    move |args: &HashMap<String, tera::Value>| implementation.call(args),
+                ),
+            );
+        }
+    }
+}
+
+/// wrap a filter, so that it fails if it's called with arguments it doesn't
+/// declare or without the arguments it requires.
+fn checked_filter(arguments: Arguments, filter: impl tera::Filter) -> impl tera::Filter {
+    move |value: &tera::Value, args: &HashMap<String, tera::Value>| {
+        arguments.check(args)?;
+        filter.filter(value, args)
+    }
+}
+
+/// like [`checked_filter`], but for functions.
+fn checked_function(arguments: Arguments, function: impl tera::Function) -> impl tera::Function {
+    move |args: &HashMap<String, tera::Value>| {
+        arguments.check(args)?;
+        function.call(args)
+    }
+}
+
+/// wrap a filter that produces colors, so that they're given the template's
+/// hex format like the colors of the palette.
+fn with_hex_format(filter: FilterFn, hex_format: HexFormat) -> impl tera::Filter {
+    move |value: &tera::Value, args: &HashMap<String, tera::Value>| {
+        let mut value = filter(value, args)?;
+        give_hex_format(&mut value, &hex_format)?;
+        Ok(value)
+    }
+}
+
+/// like [`with_hex_format`], but for functions.
+fn color_function(function: FunctionFn, hex_format: HexFormat) -> impl tera::Function {
+    move |args: &HashMap<String, tera::Value>| {
+        let mut value = function(args)?;
+        give_hex_format(&mut value, &hex_format)?;
+        Ok(value)
+    }
+}
+
+/// give a color, or a list of colors, the given hex format.
+///
+/// only the `hex` of each color is replaced, from its `rgb` and `opacity`,
+/// so the rest of the color is left as it is.
+fn give_hex_format(value: &mut tera::Value, hex_format: &HexFormat) -> tera::Result<()> {
+    if let tera::Value::Array(values) = value {
+        return values
+            .iter_mut()
+            .try_for_each(|value| give_hex_format(value, hex_format));
+    }
+
+    let channel = |value: &tera::Value| value.as_u64().and_then(|c| u8::try_from(c).ok());
+    let tera::Value::Object(color) = value else {
+        return Err(tera::Error::msg(format!("expected a color, got {value}")));
+    };
+    let (Some(r), Some(g), Some(b), Some(opacity)) = (
+        color.get("rgb").and_then(|rgb| channel(&rgb["r"])),
+        color.get("rgb").and_then(|rgb| channel(&rgb["g"])),
+        color.get("rgb").and_then(|rgb| channel(&rgb["b"])),
+        color.get("opacity").and_then(channel),
+    ) else {
+        return Err(tera::Error::msg(
+            "expected a color with `rgb` and `opacity`",
+        ));
+    };
+    color.insert(
+        "hex".to_string(),
+        tera::Value::String(hex_format.format(&RGB::new(r, g, b), opacity)),
+    );
+    Ok(())
+}
+
+/// Register each computed frontmatter value as a template on the engine.
+///
+/// Each value is a Tera expression, which is wrapped in a small template that
+/// evaluates it and serializes the result as JSON. This way the expressions are
+/// only parsed once, no matter how many times they're evaluated.
+pub fn add_computed(
+    tera: &mut tera::Tera,
+    computed: &IndexMap<String, String>,
+) -> tera::Result<()> {
+    for (name, expression) in computed {
+        tera.add_raw_template(
+            &computed_template_name(name),
+            &format!("{{% set value = {expression} %}}{{{{ value | json_encode() }}}}"),
+        )
+        .map_err(|e| tera::Error::chain(format!("Computed value `{name}` is invalid"), e))?;
+    }
+    Ok(())
+}
+
+/// Evaluate computed frontmatter values and insert them into the context.
+///
+/// Values are evaluated in the order they were declared, so each one can
+/// reference any of the values before it.
+pub fn insert_computed(
+    tera: &tera::Tera,
+    computed: &IndexMap<String, String>,
+    ctx: &mut tera::Context,
+) -> tera::Result<()> {
+    for name in computed.keys() {
+        let rendered = tera
+            .render(&computed_template_name(name), ctx)
+            .map_err(|e| tera::Error::chain(format!("Computed value `{name}` failed"), e))?;
+        let value: tera::Value = serde_json::from_str(&rendered)?;
+        ctx.insert(name, &value);
+    }
+    Ok(())
+}
+
+fn computed_template_name(name: &str) -> String {
+    format!("__whiskers_computed__{name}")
+}
+
 #[must_use]
+#[allow(clippy::too_many_lines)] // it's just a list
 pub fn all_functions() -> Vec<Function> {
     vec![
         Function {
             name: "if".to_string(),
+            implementation: FunctionImpl::Plain(functions::if_fn),
+            arguments: Arguments::Only(vec![required("cond"), required("t"), required("f")]),
             description: "Return one value if a condition is true, and another if it's false"
                 .to_string(),
             examples: vec![
@@ -85,39 +302,129 @@ pub fn all_functions() -> Vec<Function> {
         },
         Function {
             name: "object".to_string(),
+            implementation: FunctionImpl::Plain(functions::object),
+            arguments: Arguments::Any,
             description: "Create an object from the input".to_string(),
             examples: vec![
                 function_example!(object(a=1, b=2) => "{a: 1, b: 2}"),
-                function_example!(object(a=1, b=2) => "{a: 1, b: 2}"),
             ],
         },
         Function {
             name: "css_rgb".to_string(),
+            implementation: FunctionImpl::Plain(functions::css_rgb),
+            arguments: Arguments::Only(vec![required("color")]),
             description: "Convert a color to an RGB CSS string".to_string(),
             examples: vec![function_example!(css_rgb(color=red) => "rgb(210, 15, 57)")],
         },
         Function {
             name: "css_rgba".to_string(),
+            implementation: FunctionImpl::Plain(functions::css_rgba),
+            arguments: Arguments::Only(vec![required("color")]),
             description: "Convert a color to an RGBA CSS string".to_string(),
             examples: vec![function_example!(css_rgba(color=red) => "rgba(210, 15, 57, 1.00)")],
         },
         Function {
             name: "css_hsl".to_string(),
+            implementation: FunctionImpl::Plain(functions::css_hsl),
+            arguments: Arguments::Only(vec![required("color")]),
             description: "Convert a color to an HSL CSS string".to_string(),
             examples: vec![function_example!(css_hsl(color=red) => "hsl(347, 87%, 44%)")],
         },
         Function {
             name: "css_hsla".to_string(),
+            implementation: FunctionImpl::Plain(functions::css_hsla),
+            arguments: Arguments::Only(vec![required("color")]),
             description: "Convert a color to an HSLA CSS string".to_string(),
             examples: vec![function_example!(css_hsla(color=red) => "hsla(347, 87%, 44%, 1.00)")],
         },
+        Function {
+            name: "css_hwb".to_string(),
+            implementation: FunctionImpl::Plain(functions::css_hwb),
+            arguments: Arguments::Only(vec![required("color")]),
+            description: "Convert a color to an HWB CSS string".to_string(),
+            examples: vec![function_example!(css_hwb(color=red) => "hwb(347 6% 18%)")],
+        },
+        Function {
+            name: "css_lab".to_string(),
+            implementation: FunctionImpl::Plain(functions::css_lab),
+            arguments: Arguments::Only(vec![required("color")]),
+            description: "Convert a color to a CIELAB CSS string".to_string(),
+            examples: vec![function_example!(css_lab(color=red) => "lab(45.54 69.25 34.00)")],
+        },
+        Function {
+            name: "css_oklab".to_string(),
+            implementation: FunctionImpl::Plain(functions::css_oklab),
+            arguments: Arguments::Only(vec![required("color")]),
+            description: "Convert a color to an OKLab CSS string".to_string(),
+            examples: vec![function_example!(css_oklab(color=red) => "oklab(0.55 0.203 0.073)")],
+        },
+        Function {
+            name: "css_oklch".to_string(),
+            implementation: FunctionImpl::Plain(functions::css_oklch),
+            arguments: Arguments::Only(vec![required("color")]),
+            description: "Convert a color to an OKLCH CSS string".to_string(),
+            examples: vec![function_example!(css_oklch(color=red) => "oklch(0.55 0.216 19.81)")],
+        },
         Function {
             name: "rgb_array".to_string(),
+            implementation: FunctionImpl::Plain(functions::rgb_array),
+            arguments: Arguments::Only(vec![required("color")]),
             description: "Convert a color to an array of RGB values".to_string(),
             examples: vec![function_example!(rgb_array(color=red) => "[210, 15, 57]")],
         },
+        Function {
+            name: "contrast".to_string(),
+            implementation: FunctionImpl::Plain(functions::contrast),
+            arguments: Arguments::Only(vec![required("a"), required("b")]),
+            description: "Calculate the WCAG 2.x contrast ratio between two colors".to_string(),
+            examples: vec![function_example!(contrast(a=text, b=base) => "7.061999557310442")],
+        },
+        Function {
+            name: "apca".to_string(),
+            implementation: FunctionImpl::Plain(functions::apca),
+            arguments: Arguments::Only(vec![required("text"), required("background")]),
+            description: "Calculate the APCA lightness contrast (Lc) of text on a background"
+                .to_string(),
+            examples: vec![function_example!(apca(text=text, background=base) => "79.27475193644055")],
+        },
+        Function {
+            name: "apca_readable".to_string(),
+            implementation: FunctionImpl::Plain(functions::apca_readable),
+            arguments: Arguments::Only(vec![required("text"), required("background"), required("size"), optional("weight")]),
+            description: "Check if text of a font size (px) and weight is readable on a background using APCA"
+                .to_string(),
+            examples: vec![
+                function_example!(apca_readable(text=text, background=base, size=16) => "false"),
+                function_example!(apca_readable(text=text, background=base, size=16, weight=700) => "true"),
+            ],
+        },
+        Function {
+            name: "color".to_string(),
+            implementation: FunctionImpl::Color(functions::color),
+            arguments: Arguments::Only(vec![optional("hex"), optional("rgb"), optional("hsl")]),
+            description: "Make a color from a hex string, RGB channels or HSL".to_string(),
+            examples: vec![
+                function_example!(color(hex="#ff8800") => "#ff8800"),
+                function_example!(color(rgb=[255, 136, 0]) => "#ff8800"),
+            ],
+        },
+        Function {
+            name: "gradient".to_string(),
+            implementation: FunctionImpl::Color(functions::gradient),
+            arguments: Arguments::Only(vec![required("from"), required("to"), required("steps"), optional("space")]),
+            description: "Generate a number of colors evenly spaced between two colors".to_string(),
+            examples: vec![
+                function_example!(gradient(from=red, to=blue, steps=3) => "[#d20f39, #8f5c9d, #1e66f5]"),
+            ],
+        },
         Function {
             name: "read_file".to_string(),
+            implementation: FunctionImpl::Handler(|environment| {
+                Box::new(functions::read_file_handler(
+                    environment.template_directory.to_owned(),
+                ))
+            }),
+            arguments: Arguments::Only(vec![required("path")]),
             description:
                 "Read and include the contents of a file, path is relative to the template file"
                     .to_string(),
@@ -127,39 +434,106 @@ pub fn all_functions() -> Vec<Function> {
 }
 
 #[must_use]
+#[allow(clippy::too_many_lines)] // it's just a list
 pub fn all_filters() -> Vec<Filter> {
     vec![
         Filter {
             name: "add".to_string(),
+            implementation: FilterImpl::Color(filters::add),
+            arguments: Arguments::Only(vec![
+                optional("hue"),
+                optional("saturation"),
+                optional("lightness"),
+                optional("opacity"),
+                optional("chroma"),
+                optional("perceptual_lightness"),
+            ]),
             description: "Add a value to a color".to_string(),
             examples: vec![
                 filter_example!(red | add(hue=30) => "#ff6666"),
                 filter_example!(red | add(saturation=0.5) => "#ff6666"),
+                filter_example!(red | add(perceptual_lightness=10) => "#f74256"),
             ],
         },
         Filter {
             name: "sub".to_string(),
+            implementation: FilterImpl::Color(filters::sub),
+            arguments: Arguments::Only(vec![
+                optional("hue"),
+                optional("saturation"),
+                optional("lightness"),
+                optional("opacity"),
+                optional("chroma"),
+                optional("perceptual_lightness"),
+            ]),
             description: "Subtract a value from a color".to_string(),
             examples: vec![
                 filter_example!(red | sub(hue=30) => "#d30f9b"),
                 filter_example!(red | sub(saturation=60) => "#8f5360"),
+                filter_example!(red | sub(chroma=0.1) => "#ab5355"),
             ],
         },
         Filter {
             name: "mod".to_string(),
+            implementation: FilterImpl::Color(filters::modify),
+            arguments: Arguments::Only(vec![
+                optional("hue"),
+                optional("saturation"),
+                optional("lightness"),
+                optional("opacity"),
+                optional("chroma"),
+                optional("perceptual_lightness"),
+            ]),
             description: "Modify a color".to_string(),
             examples: vec![
                 filter_example!(red | mod(lightness=80) => "#f8a0b3"),
                 filter_example!(red | mod(opacity=0.5) => "#d20f3980"),
+                filter_example!(red | mod(chroma=0.1) => "#a45859"),
             ],
         },
         Filter {
             name: "mix".to_string(),
+            implementation: FilterImpl::Color(filters::mix),
+            arguments: Arguments::Only(vec![required("color"), required("amount")]),
             description: "Mix two colors together".to_string(),
             examples: vec![filter_example!(red | mix(color=base, amount=0.5) => "#e08097")],
         },
+        Filter {
+            name: "ramp".to_string(),
+            implementation: FilterImpl::Color(filters::ramp),
+            arguments: Arguments::Only(vec![
+                optional("tints"),
+                optional("shades"),
+                optional("amount"),
+                optional("space"),
+            ]),
+            description:
+                "Generate tints and shades of a color, from lightest to darkest, mixing in Oklab by default"
+                    .to_string(),
+            examples: vec![
+                filter_example!(red | ramp(tints=1, shades=1) => "[#fdd7d6, #d20f39, #100001]"),
+            ],
+        },
+        Filter {
+            name: "flatten".to_string(),
+            implementation: FilterImpl::Color(filters::flatten),
+            arguments: Arguments::Only(vec![required("on"), optional("mode")]),
+            description:
+                "Composite a translucent color onto a background, optionally blending them"
+                    .to_string(),
+            examples: vec![filter_example!(red | flatten(on=blue, mode="multiply") => "#190637")],
+        },
+        Filter {
+            name: "to_color".to_string(),
+            implementation: FilterImpl::Color(filters::to_color),
+            arguments: Arguments::Only(vec![]),
+            description: "Convert a CSS color string, RGB channels or HSL into a color".to_string(),
+            examples: vec![filter_example!("rgb(255, 136, 0)" | to_color => "#ff8800")],
+        },
         Filter {
             name: "urlencode_lzma".to_string(),
+            implementation: FilterImpl::Plain(filters::urlencode_lzma),
+            arguments: Arguments::Only(vec![]),
             description: "Serialize an object into a URL-safe string with LZMA compression"
                 .to_string(),
             examples: vec![
@@ -168,57 +542,235 @@ pub fn all_filters() -> Vec<Filter> {
         },
         Filter {
             name: "trunc".to_string(),
+            implementation: FilterImpl::Plain(filters::trunc),
+            arguments: Arguments::Only(vec![required("places")]),
             description: "Truncate a number to a certain number of places".to_string(),
             examples: vec![filter_example!(1.123456 | trunc(places=3) => "1.123")],
         },
         Filter {
             name: "hex".to_string(),
+            implementation: FilterImpl::Plain(filters::hex),
+            arguments: Arguments::Only(vec![optional("format")]),
             description: "Fetch a colour's hex representation. Shortcut for `get(key=\"hex\")`"
                 .to_string(),
-            examples: vec![filter_example!(red | hex => "d20f39")],
+            examples: vec![
+                filter_example!(red | hex => "d20f39"),
+                filter_example!(red | hex(format="0x{{R}}{{G}}{{B}}") => "0xD20F39"),
+            ],
         },
         Filter {
             name: "css_rgb".to_string(),
+            implementation: FilterImpl::Plain(filters::css_rgb),
+            arguments: Arguments::Only(vec![]),
             description: "Convert a color to an RGB CSS string".to_string(),
             examples: vec![filter_example!(red | css_rgb => "rgb(210, 15, 57)")],
         },
         Filter {
             name: "css_rgba".to_string(),
+            implementation: FilterImpl::Plain(filters::css_rgba),
+            arguments: Arguments::Only(vec![]),
             description: "Convert a color to an RGBA CSS string".to_string(),
             examples: vec![filter_example!(red | css_rgba => "rgba(210, 15, 57, 1.00)")],
         },
         Filter {
             name: "css_hsl".to_string(),
+            implementation: FilterImpl::Plain(filters::css_hsl),
+            arguments: Arguments::Only(vec![]),
             description: "Convert a color to an HSL CSS string".to_string(),
             examples: vec![filter_example!(red | css_hsl => "hsl(347, 87%, 44%)")],
         },
         Filter {
             name: "css_hsla".to_string(),
+            implementation: FilterImpl::Plain(filters::css_hsla),
+            arguments: Arguments::Only(vec![]),
             description: "Convert a color to an HSLA CSS string".to_string(),
             examples: vec![filter_example!(red | css_hsla => "hsla(347, 87%, 44%, 1.00)")],
         },
+        Filter {
+            name: "css_hwb".to_string(),
+            implementation: FilterImpl::Plain(filters::css_hwb),
+            arguments: Arguments::Only(vec![]),
+            description: "Convert a color to an HWB CSS string".to_string(),
+            examples: vec![filter_example!(red | css_hwb => "hwb(347 6% 18%)")],
+        },
+        Filter {
+            name: "css_lab".to_string(),
+            implementation: FilterImpl::Plain(filters::css_lab),
+            arguments: Arguments::Only(vec![]),
+            description: "Convert a color to a CIELAB CSS string".to_string(),
+            examples: vec![filter_example!(red | css_lab => "lab(45.54 69.25 34.00)")],
+        },
+        Filter {
+            name: "css_oklab".to_string(),
+            implementation: FilterImpl::Plain(filters::css_oklab),
+            arguments: Arguments::Only(vec![]),
+            description: "Convert a color to an OKLab CSS string".to_string(),
+            examples: vec![filter_example!(red | css_oklab => "oklab(0.55 0.203 0.073)")],
+        },
+        Filter {
+            name: "css_oklch".to_string(),
+            implementation: FilterImpl::Plain(filters::css_oklch),
+            arguments: Arguments::Only(vec![]),
+            description: "Convert a color to an OKLCH CSS string".to_string(),
+            examples: vec![filter_example!(red | css_oklch => "oklch(0.55 0.216 19.81)")],
+        },
         Filter {
             name: "rgb_array".to_string(),
+            implementation: FilterImpl::Plain(filters::rgb_array),
+            arguments: Arguments::Only(vec![]),
             description: "Convert a color to an array of RGB values".to_string(),
             examples: vec![filter_example!(red | rgb_array => "[210, 15, 57]")],
         },
+        Filter {
+            name: "readable_on".to_string(),
+            implementation: FilterImpl::Handler(|environment| {
+                Box::new(filters::readable_on_handler(environment.flavor))
+            }),
+            arguments: Arguments::Only(vec![optional("candidates"), optional("min_ratio")]),
+            description: "Pick the candidate color with the highest contrast on a background"
+                .to_string(),
+            examples: vec![
+                filter_example!(red | readable_on => "base"),
+                filter_example!(blue | readable_on(candidates=[text, base], min_ratio=3) => "base"),
+            ],
+        },
+        Filter {
+            name: "simulate".to_string(),
+            implementation: FilterImpl::Color(filters::simulate),
+            arguments: Arguments::Only(vec![required("deficiency")]),
+            description: "Simulate how a color looks with a color vision deficiency".to_string(),
+            examples: vec![filter_example!(red | simulate(deficiency="deuteranopia") => "#847732")],
+        },
+        Filter {
+            name: "xterm256".to_string(),
+            implementation: FilterImpl::Plain(filters::xterm256),
+            arguments: Arguments::Only(vec![]),
+            description: "Find the nearest color in the xterm 256-color palette".to_string(),
+            examples: vec![filter_example!(red | xterm256 => "161")],
+        },
+        Filter {
+            name: "ansi16".to_string(),
+            implementation: FilterImpl::Plain(filters::ansi16),
+            arguments: Arguments::Only(vec![]),
+            description: "Find the nearest of the 16 basic ANSI colors".to_string(),
+            examples: vec![filter_example!(red | ansi16 => "1")],
+        },
+        Filter {
+            name: "ansi".to_string(),
+            implementation: FilterImpl::Plain(filters::ansi),
+            arguments: Arguments::Only(vec![optional("layer"), optional("mode")]),
+            description: "Convert a color to an ANSI SGR escape sequence".to_string(),
+            examples: vec![
+                filter_example!(red | ansi => "\\x1b[38;2;210;15;57m"),
+                filter_example!(red | ansi(layer="bg", mode="256") => "\\x1b[48;5;161m"),
+            ],
+        },
     ]
 }
 
+/// A Tera function, declared once to both register it and list it with
+/// `--list-functions`.
 #[derive(serde::Serialize)]
 pub struct Function {
     pub name: String,
+    #[serde(skip)]
+    pub implementation: FunctionImpl,
+    pub arguments: Arguments,
     pub description: String,
     pub examples: Vec<FunctionExample>,
 }
 
+/// A Tera filter, declared once to both register it and list it with
+/// `--list-functions`.
 #[derive(serde::Serialize)]
 pub struct Filter {
     pub name: String,
+    #[serde(skip)]
+    pub implementation: FilterImpl,
+    pub arguments: Arguments,
     pub description: String,
     pub examples: Vec<FilterExample>,
 }
 
+/// The named arguments a filter or function accepts.
+pub enum Arguments {
+    /// only these arguments are accepted.
+    Only(Vec<Argument>),
+    /// any arguments are accepted, like the keys of `object`.
+    Any,
+}
+
+impl Arguments {
+    /// check the arguments of a call against the declared arguments, so that
+    /// missing arguments give a clear error.
+    ///
+    /// unknown arguments are allowed, as tera has always ignored them and
+    /// existing templates may rely on that.
+    fn check(&self, args: &HashMap<String, tera::Value>) -> tera::Result<()> {
+        let Self::Only(declared) = self else {
+            return Ok(());
+        };
+        if let Some(missing) = declared
+            .iter()
+            .find(|argument| argument.required && !args.contains_key(&argument.name))
+        {
+            return Err(tera::Error::msg(format!(
+                "argument `{}` is required",
+                missing.name
+            )));
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    fn accepts(&self, name: &str) -> bool {
+        match self {
+            Self::Only(declared) => declared.iter().any(|argument| argument.name == name),
+            Self::Any => true,
+        }
+    }
+}
+
+impl serde::Serialize for Arguments {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Only(arguments) => arguments.serialize(serializer),
+            Self::Any => serializer.serialize_str("any"),
+        }
+    }
+}
+
+/// A named argument of a filter or function.
+#[derive(serde::Serialize)]
+pub struct Argument {
+    pub name: String,
+    pub required: bool,
+}
+
+fn required(name: &str) -> Argument {
+    Argument {
+        name: name.to_string(),
+        required: true,
+    }
+}
+
+fn optional(name: &str) -> Argument {
+    Argument {
+        name: name.to_string(),
+        required: false,
+    }
+}
+
+impl std::fmt::Display for Argument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.required {
+            write!(f, "`{}`", self.name)
+        } else {
+            write!(f, "`{}` (optional)", self.name)
+        }
+    }
+}
+
 #[derive(serde::Serialize)]
 pub struct FunctionExample {
     pub inputs: IndexMap<String, String>,
@@ -232,10 +784,19 @@ pub struct FilterExample {
     pub output: String,
 }
 
+fn display_arguments(arguments: &Arguments) -> String {
+    match arguments {
+        Arguments::Only(arguments) if arguments.is_empty() => "None".to_string(),
+        Arguments::Only(arguments) => arguments.iter().join(", "),
+        Arguments::Any => "Any".to_string(),
+    }
+}
+
 impl markdown::TableDisplay for Function {
     fn table_headings() -> Box<[String]> {
         Box::new([
             "Name".to_string(),
+            "Arguments".to_string(),
             "Description".to_string(),
             "Examples".to_string(),
         ])
@@ -244,6 +805,7 @@ impl markdown::TableDisplay for Function {
     fn table_row(&self) -> Box<[String]> {
         Box::new([
             format!("`{}`", self.name),
+            display_arguments(&self.arguments),
             self.description.clone(),
             if self.examples.is_empty() {
                 "None".to_string()
@@ -269,6 +831,7 @@ impl markdown::TableDisplay for Filter {
     fn table_headings() -> Box<[String]> {
         Box::new([
             "Name".to_string(),
+            "Arguments".to_string(),
             "Description".to_string(),
             "Examples".to_string(),
         ])
@@ -277,6 +840,7 @@ impl markdown::TableDisplay for Filter {
     fn table_row(&self) -> Box<[String]> {
         Box::new([
             format!("`{}`", self.name),
+            display_arguments(&self.arguments),
             self.description.clone(),
             if self.examples.is_empty() {
                 "None".to_string()
@@ -310,20 +874,54 @@ impl markdown::TableDisplay for Filter {
 
 #[cfg(test)]
 mod tests {
+    use itertools::Itertools as _;
+
     #[test]
     fn function_example_with_single_arg() {
-        let example = function_example!(mix(base=base) => "#804040");
-        assert_eq!(example.inputs["base"], "base");
-        assert_eq!(example.output, "#804040");
+        let example = function_example!(css_rgb(color=red) => "rgb(210, 15, 57)");
+        assert_eq!(example.inputs["color"], "red");
+        assert_eq!(example.output, "rgb(210, 15, 57)");
     }
 
     #[test]
     fn function_example_with_multiple_args() {
-        let example = function_example!(mix(base=base, blend=red, amount=0.5) => "#804040");
-        assert_eq!(example.inputs["base"], "base");
-        assert_eq!(example.inputs["blend"], "red");
-        assert_eq!(example.inputs["amount"], "0.5");
-        assert_eq!(example.output, "#804040");
+        let example = function_example!(gradient(from=red, to=blue, steps=3) => "[#d20f39, #8f5c9d, #1e66f5]");
+        assert_eq!(example.inputs["from"], "red");
+        assert_eq!(example.inputs["to"], "blue");
+        assert_eq!(example.inputs["steps"], "3");
+        assert_eq!(example.output, "[#d20f39, #8f5c9d, #1e66f5]");
+    }
+
+    #[test]
+    fn names_are_unique() {
+        assert!(super::all_functions().iter().map(|f| &f.name).all_unique());
+        assert!(super::all_filters().iter().map(|f| &f.name).all_unique());
+    }
+
+    #[test]
+    fn examples_only_use_declared_arguments() {
+        for function in super::all_functions() {
+            for example in &function.examples {
+                for input in example.inputs.keys() {
+                    assert!(
+                        function.arguments.accepts(input),
+                        "{} has no argument {input}",
+                        function.name
+                    );
+                }
+            }
+        }
+        for filter in super::all_filters() {
+            for example in &filter.examples {
+                for input in example.inputs.keys() {
+                    assert!(
+                        filter.arguments.accepts(input),
+                        "{} has no argument {input}",
+                        filter.name
+                    );
+                }
+            }
+        }
     }
 
     #[test]
@@ -350,4 +948,18 @@ mod tests {
         assert_eq!(example.inputs["saturation"], "0.5");
         assert_eq!(example.output, "#ff6666");
     }
+
+    #[test]
+    fn give_hex_format_only_replaces_hex() {
+        let color = crate::models::Color::from_value(&tera::Value::from("#d20f3980"))
+            .expect("color is valid");
+        let mut value = tera::to_value([&color, &color]).expect("colors serialize");
+        let hex_format =
+            super::HexFormat::parse("0x{{A}}{{R}}{{G}}{{B}}").expect("hex format is valid");
+        super::give_hex_format(&mut value, &hex_format).expect("value holds colors");
+
+        let mut expected = tera::to_value(&color).expect("color serializes");
+        expected["hex"] = "0x80D20F39".into();
+        assert_eq!(value, tera::Value::Array(vec![expected.clone(), expected]));
+    }
 }
@@ -55,20 +55,931 @@ mod happy_path {
             .stderr(predicate::str::is_empty());
     }
 
+    /// Test that the CLI merges frontmatter from extended files
+    #[test]
+    fn test_extends() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args(["tests/fixtures/extends/extends.tera", "-f", "latte"])
+            .assert();
+        assert
+            .success()
+            .stdout(include_str!("fixtures/extends/extends.md"))
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that the CLI evaluates computed frontmatter values
+    #[test]
+    fn test_computed() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args(["tests/fixtures/computed/computed.tera", "-f", "latte"])
+            .assert();
+        assert
+            .success()
+            .stdout(include_str!("fixtures/computed/computed.md"))
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that computed frontmatter values are evaluated per matrix combination
+    #[test]
+    fn test_computed_matrix() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args(["--dry-run", "tests/fixtures/computed/matrix.tera"])
+            .assert();
+        assert
+            .success()
+            .stdout(
+                predicate::str::contains("catppuccin-latte-ffffff.txt")
+                    .and(predicate::str::contains("catppuccin-mocha-28283e.txt")),
+            )
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that unknown filter and function arguments are ignored, as in older versions
+    #[test]
+    fn test_unknown_arguments_ignored() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .write_stdin("{{ red | hex(hue2=10) }} {{ css_rgb(color=red, alpha=1) }}")
+            .args(["-", "-f", "mocha"])
+            .assert();
+        assert
+            .success()
+            .stdout("f38ba8 rgb(243, 139, 168)")
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that `rgb_array` works as both a filter and a function
+    #[test]
+    fn test_rgb_array() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .write_stdin(
+                "{{ red | rgb_array | join(sep=\",\") }} {{ rgb_array(color=base) | first }}",
+            )
+            .args(["-", "-f", "mocha"])
+            .assert();
+        assert
+            .success()
+            .stdout("243,139,168 30")
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that `--set` and `--set-json` override frontmatter and matrix iterables
+    #[test]
+    fn test_set_overrides() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args([
+                "--dry-run",
+                "tests/fixtures/multifile.tera",
+                "--set",
+                "accent=blue",
+                "--set-json",
+                r#"variant=["normal"]"#,
+            ])
+            .assert();
+        assert
+            .success()
+            .stdout(
+                predicate::str::contains("catppuccin-mocha-blue-normal.ini")
+                    .and(predicate::str::contains("no-italics").not())
+                    .and(predicate::str::contains("-red-").not()),
+            )
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that `--set` builds nested objects and takes precedence over `--overrides`
+    #[test]
+    fn test_set_nested_overrides() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .write_stdin("{{ a.b }} {{ a.c }} {{ a.d }} {{ a.e + 1 }}")
+            .args([
+                "-",
+                "--overrides",
+                r#"{"a": {"b": "x", "c": "y"}}"#,
+                "--set",
+                "a.b=z",
+                "--set",
+                "a.d=true",
+                "--set",
+                "a.e=41",
+            ])
+            .assert();
+        assert
+            .success()
+            .stdout("z y true 42")
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that `--set` and `--set-json` are applied in command-line order
+    #[test]
+    fn test_set_overrides_in_order() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .write_stdin("{{ a }} {{ b }}")
+            .args([
+                "-",
+                "--set-json",
+                r#"a="json""#,
+                "--set",
+                "a=set",
+                "--set",
+                "b=set",
+                "--set-json",
+                r#"b="json""#,
+            ])
+            .assert();
+        assert
+            .success()
+            .stdout("set json")
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that overrides can be read from YAML and TOML files and are merged in order
+    #[test]
+    fn test_override_files() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args([
+                "tests/fixtures/overrides/overrides.tera",
+                "-f",
+                "mocha",
+                "--overrides",
+                "tests/fixtures/overrides/overrides.yml",
+                "--overrides",
+                "tests/fixtures/overrides/overrides.toml",
+                "--color-overrides",
+                "tests/fixtures/overrides/colors.yml",
+                "--color-overrides",
+                "tests/fixtures/overrides/colors.toml",
+            ])
+            .assert();
+        assert
+            .success()
+            .stdout("from yaml / from toml / italics=true bold=false\ntext=ff0000 base=111111\n")
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that repeated color overrides deep-merge the fields of derived flavors
+    #[test]
+    fn test_color_override_files_merge_derived() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .write_stdin("{{ flavor.name }} {{ flavor.emoji }} {{ base.hex }}")
+            .args([
+                "-",
+                "-f",
+                "mocha-oled",
+                "--color-overrides",
+                "tests/fixtures/derived/derived.yml",
+                "--color-overrides",
+                "tests/fixtures/derived/rename.toml",
+            ])
+            .assert();
+        assert
+            .success()
+            .stdout("Midnight 🖤 000000")
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that overrides can be set through environment variables, below the CLI
+    #[test]
+    fn test_env_overrides() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .write_stdin("{{ accent }} {{ a.b }} {{ a.c }} {{ text.hex }} {{ base.hex }}")
+            .env(
+                "WHISKERS_OVERRIDES",
+                r#"{"accent": "red", "a": {"b": 1, "c": 1}}"#,
+            )
+            .env("WHISKERS_SET_a__b", "2")
+            .env("WHISKERS_COLOR_OVERRIDES", r#"{"all": {"text": "123456"}}"#)
+            .args([
+                "-",
+                "-f",
+                "mocha",
+                "--overrides",
+                r#"{"accent": "blue"}"#,
+                "--color-overrides",
+                r#"{"mocha": {"base": "000000"}}"#,
+            ])
+            .assert();
+        assert
+            .success()
+            .stdout("blue 2 1 123456 000000")
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that `WHISKERS_SET_*` variables are applied below every CLI override
+    #[test]
+    fn test_env_set_below_cli_overrides() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .write_stdin("{{ accent }} {{ a.b }} {{ a.c }}")
+            .env("WHISKERS_OVERRIDES", r#"{"a": {"c": 1}}"#)
+            .env("WHISKERS_SET_accent", "red")
+            .env("WHISKERS_SET_a__b", "1")
+            .env("WHISKERS_SET_a__c", "2")
+            .args([
+                "-",
+                "-f",
+                "mocha",
+                "--overrides",
+                r#"{"accent": "blue", "a": {"b": 3}}"#,
+            ])
+            .assert();
+        assert
+            .success()
+            .stdout("blue 3 2")
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that color overrides accept any CSS color syntax, including alpha
+    #[test]
+    fn test_css_color_overrides() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .write_stdin(
+                "{% for c in [red, green, blue, text, base, crust] %}{{ c.hex }}/{{ c.opacity }} {% endfor %}",
+            )
+            .args([
+                "-",
+                "-f",
+                "mocha",
+                "--color-overrides",
+                r##"{"mocha": {
+                    "red": "#f00",
+                    "green": "00ff0080",
+                    "blue": "rgb(0 0 255 / 50%)",
+                    "text": "hsl(0, 0%, 100%)",
+                    "base": "black",
+                    "crust": "#1e1e2e"
+                }}"##,
+            ])
+            .assert();
+        assert
+            .success()
+            .stdout("ff0000/255 00ff0080/128 0000ff80/128 ffffff/255 000000/255 1e1e2e/255 ")
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that extra colors are added to every flavor and can be overridden per flavor
+    #[test]
+    fn test_extra_colors() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .write_stdin(
+                "{% for _, f in flavors %}{{ f.colors.brand.hex }} {% endfor %}{{ brand.order }} {{ brand.accent }}",
+            )
+            .args([
+                "-",
+                "-f",
+                "mocha",
+                "--color-overrides",
+                r##"{"extra": {"brand": "#ff00ff"}, "latte": {"brand": "#800080"}}"##,
+            ])
+            .assert();
+        assert
+            .success()
+            .stdout("800080 ff00ff ff00ff ff00ff 26 false")
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that the CLI can render against a custom palette file
+    #[test]
+    fn test_custom_palette() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args([
+                "tests/fixtures/palette/palette.tera",
+                "-f",
+                "mocha",
+                "--palette",
+                "tests/fixtures/palette/oled.yml",
+            ])
+            .assert();
+        assert
+            .success()
+            .stdout(include_str!("fixtures/palette/palette.md"))
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that derived flavors can be selected and appear alongside the builtin flavors
+    #[test]
+    fn test_derived_flavors() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args([
+                "tests/fixtures/derived/derived.tera",
+                "-f",
+                "mocha-oled",
+                "--color-overrides",
+                "tests/fixtures/derived/derived.yml",
+            ])
+            .assert();
+        assert
+            .success()
+            .stdout(include_str!("fixtures/derived/derived.md"))
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that derived flavors are included in the `flavor` matrix iterable
+    #[test]
+    fn test_derived_flavors_matrix() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args([
+                "--dry-run",
+                "tests/fixtures/derived/matrix.tera",
+                "--color-overrides",
+                "tests/fixtures/derived/derived.yml",
+            ])
+            .assert();
+        assert
+            .success()
+            .stdout(
+                predicate::str::contains("catppuccin-mocha.txt")
+                    .and(predicate::str::contains("catppuccin-mocha-oled.txt"))
+                    .and(predicate::str::contains("catppuccin-latte-muted.txt")),
+            )
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that `--list-flavors` includes derived flavors
+    #[test]
+    fn test_list_derived_flavors() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args([
+                "--list-flavors",
+                "-o",
+                "plain",
+                "--color-overrides",
+                "tests/fixtures/derived/derived.yml",
+            ])
+            .assert();
+        assert
+            .success()
+            .stdout("latte\nfrappe\nmacchiato\nmocha\nmocha-oled\nlatte-muted\n")
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that `--list-colors` lists the effective palette
+    #[test]
+    fn test_list_colors() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args([
+                "--list-colors",
+                "-f",
+                "latte",
+                "-o",
+                "plain",
+                "--color-overrides",
+                r#"{"all": {"base": "000"}}"#,
+            ])
+            .assert();
+        assert
+            .success()
+            .stdout(
+                predicate::str::contains("latte base 000000\n")
+                    .and(predicate::str::contains("latte red d20f39\n"))
+                    .and(predicate::str::contains("mocha").not()),
+            )
+            .stderr(predicate::str::is_empty());
+
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args(["--list-colors", "-f", "mocha", "-o", "markdown"])
+            .assert();
+        assert.success().stdout(predicate::str::contains(
+            "| mauve      | Mauve     | true   | `#cba6f7` | `rgb(203, 166, 247)` | `hsl(267, 84%, 81%)` |",
+        ));
+    }
+
+    /// Test that the palette can be exported as a GIMP palette
+    #[test]
+    fn test_export_gpl() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd.args(["--export", "gpl", "-f", "mocha"]).assert();
+        assert
+            .success()
+            .stdout(include_str!("fixtures/export/mocha.gpl"))
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that CSS exports respect color overrides
+    #[test]
+    fn test_export_css() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args([
+                "--export",
+                "css",
+                "-f",
+                "latte",
+                "--color-overrides",
+                r##"{"extra": {"brand": "#ff00ff80"}}"##,
+            ])
+            .assert();
+        assert
+            .success()
+            .stdout(include_str!("fixtures/export/latte.css"))
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that ASE exports hold a named swatch group of every color per flavor
+    #[test]
+    fn test_export_ase() {
+        type Swatch = (String, [f32; 3]);
+
+        // read a length-prefixed, null-terminated UTF-16 name
+        fn read_name(data: &[u8]) -> (String, &[u8]) {
+            let len = usize::from(u16::from_be_bytes([data[0], data[1]]));
+            let units = data[2..2 + len * 2]
+                .chunks(2)
+                .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                .take_while(|unit| *unit != 0)
+                .collect::<Vec<_>>();
+            (
+                String::from_utf16(&units).expect("name is valid UTF-16"),
+                &data[2 + len * 2..],
+            )
+        }
+
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let output = cmd
+            .args(["--export", "ase"])
+            .output()
+            .expect("command runs");
+        assert!(output.status.success());
+        let (header, mut blocks) = output.stdout.split_at(12);
+        // 4 flavors of 26 colors, plus a group start & end block per flavor
+        assert_eq!(*header, *b"ASEF\x00\x01\x00\x00\x00\x00\x00\x70");
+
+        let mut groups: Vec<(String, Vec<Swatch>)> = Vec::new();
+        while !blocks.is_empty() {
+            let kind = u16::from_be_bytes([blocks[0], blocks[1]]);
+            let len = u32::from_be_bytes([blocks[2], blocks[3], blocks[4], blocks[5]]) as usize;
+            let data = &blocks[6..6 + len];
+            blocks = &blocks[6 + len..];
+            match kind {
+                0xC001 => groups.push((read_name(data).0, Vec::new())),
+                0xC002 => assert!(data.is_empty()),
+                0x0001 => {
+                    let (name, data) = read_name(data);
+                    assert_eq!(data[..4], *b"RGB ");
+                    let rgb = [4, 8, 12].map(|i| {
+                        f32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]])
+                    });
+                    groups
+                        .last_mut()
+                        .expect("colors are inside a group")
+                        .1
+                        .push((name, rgb));
+                }
+                _ => panic!("unexpected block type {kind:#x}"),
+            }
+        }
+
+        assert_eq!(
+            groups
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "Catppuccin Latte",
+                "Catppuccin Frappé",
+                "Catppuccin Macchiato",
+                "Catppuccin Mocha"
+            ]
+        );
+        assert!(groups.iter().all(|(_, colors)| colors.len() == 26));
+        let (_, mocha) = &groups[3];
+        assert_eq!(
+            mocha[0],
+            (
+                "Rosewater".to_string(),
+                [245.0 / 255.0, 224.0 / 255.0, 220.0 / 255.0]
+            )
+        );
+        assert_eq!(
+            mocha[25],
+            (
+                "Crust".to_string(),
+                [17.0 / 255.0, 17.0 / 255.0, 27.0 / 255.0]
+            )
+        );
+    }
+
+    /// Test that Procreate exports hold a single palette of HSB swatches
+    #[test]
+    fn test_export_procreate() {
+        use std::io::Read as _;
+
+        let swatches_json = |args: &[&str]| {
+            let mut cmd = cargo_bin_cmd!("whiskers");
+            let output = cmd
+                .args(["--export", "procreate"])
+                .args(args)
+                .output()
+                .expect("command runs");
+            assert!(output.status.success());
+            let mut archive = zip::ZipArchive::new(std::io::Cursor::new(output.stdout))
+                .expect("export is a zip archive");
+            let mut json = String::new();
+            archive
+                .by_name("Swatches.json")
+                .expect("archive holds Swatches.json")
+                .read_to_string(&mut json)
+                .expect("Swatches.json is UTF-8");
+            serde_json::from_str::<serde_json::Value>(&json).expect("Swatches.json is JSON")
+        };
+
+        let palettes = swatches_json(&["-f", "frappe"]);
+        assert_eq!(palettes.as_array().map(Vec::len), Some(1));
+        assert_eq!(palettes[0]["name"], "Catppuccin Frappé");
+        let swatches = palettes[0]["swatches"]
+            .as_array()
+            .expect("swatches is an array");
+        assert_eq!(swatches.len(), 26);
+        let assert_hsba = |swatch: &serde_json::Value, expected: [f64; 4]| {
+            for (key, expected) in ["hue", "saturation", "brightness", "alpha"]
+                .into_iter()
+                .zip(expected)
+            {
+                let value = swatch[key].as_f64().expect("channels are numbers");
+                assert!((value - expected).abs() < 1e-9, "{key} is {value}");
+            }
+            assert_eq!(swatch["colorSpace"], 0);
+        };
+        // rosewater is f2d5cf, so red is the largest channel
+        assert_hsba(
+            &swatches[0],
+            [
+                (213.0 - 207.0) / (242.0 - 207.0) / 6.0,
+                (242.0 - 207.0) / 242.0,
+                242.0 / 255.0,
+                1.0,
+            ],
+        );
+
+        // extra colors can fill the palette up to Procreate's 30 swatch limit
+        let palettes = swatches_json(&[
+            "-f",
+            "latte",
+            "--color-overrides",
+            r#"{"extra": {"a": "fff", "b": "fff", "c": "fff", "d": "80000080"}}"#,
+        ]);
+        let swatches = palettes[0]["swatches"]
+            .as_array()
+            .expect("swatches is an array");
+        assert_eq!(swatches.len(), 30);
+        assert_hsba(&swatches[29], [0.0, 1.0, 128.0 / 255.0, 128.0 / 255.0]);
+    }
+
     /// Test that the CLI can render colours in specific formats
     #[test]
-    fn test_formats() {
+    fn test_formats() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args(["tests/fixtures/formats.tera", "-f", "latte"])
+            .assert();
+        assert
+            .success()
+            .stdout(
+                predicate::str::contains("24-bit red: 13766457")
+                    .and(predicate::str::contains("unsigned 32-bit red: 4291956537"))
+                    .and(predicate::str::contains("signed 32-bit red: -3010759"))
+                    .and(predicate::str::contains("channels: 210 15 57"))
+                    .and(predicate::str::contains("BGR red: 3739602"))
+                    .and(predicate::str::contains("ABGR red: 4281929682"))
+                    .and(predicate::str::contains(
+                        "normalized red: 0.8235294117647058 0.058823529411764705 0.2235294117647059 1",
+                    ))
+                    .and(predicate::str::contains("16-bit red: 53970 3855 14649 65535"))
+                    .and(predicate::str::contains("X11 red: rgb:d2d2/0f0f/3939"))
+                    .and(predicate::str::contains("translucent ABGR red: 2151223250"))
+                    .and(predicate::str::contains(
+                        "translucent normalized red: 0.5019607843137255",
+                    ))
+                    .and(predicate::str::contains("translucent 16-bit red: 32896"))
+                    .and(predicate::str::contains("premultiplied red: 105 8 29")),
+            )
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that colors can be rendered and adjusted in `OKLab` & `OKLCH`
+    #[test]
+    fn test_oklch() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args(["tests/fixtures/oklch.tera", "-f", "latte"])
+            .assert();
+        assert
+            .success()
+            .stdout(
+                predicate::str::contains("oklab: oklab(0.55 0.203 0.073)")
+                    .and(predicate::str::contains("oklch: oklch(0.55 0.216 19.81)"))
+                    .and(predicate::str::contains("fields: 0.550 0.216 19.8"))
+                    .and(predicate::str::contains(
+                        "perceptual lightness: ffaf4f 0047cc",
+                    ))
+                    .and(predicate::str::contains("chroma: 727272 d40037"))
+                    .and(predicate::str::contains(
+                        "translucent: oklch(0.55 0.216 19.81 / 50%)",
+                    )),
+            )
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that colors are available in HSV, HWB, CIELAB & CMYK
+    #[test]
+    fn test_color_representations() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args(["tests/fixtures/representations.tera", "-f", "latte"])
+            .assert();
+        assert
+            .success()
+            .stdout(
+                predicate::str::contains("hsv: 347 0.929 0.824")
+                    .and(predicate::str::contains("hwb: hwb(347 6% 18%)"))
+                    .and(predicate::str::contains("lab: lab(45.54 69.25 34.00)"))
+                    .and(predicate::str::contains("cmyk: 0.000 0.929 0.729 0.176"))
+                    .and(predicate::str::contains("gray: hwb(0 65% 35%)"))
+                    .and(predicate::str::contains(
+                        "translucent: lab(45.54 69.25 34.00 / 50%)",
+                    )),
+            )
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that contrast ratios are computed and readable colors are picked
+    #[test]
+    fn test_contrast() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args(["tests/fixtures/contrast.tera", "-f", "mocha"])
+            .assert();
+        assert
+            .success()
+            .stdout(
+                predicate::str::contains("contrast: 11.34 11.34 1.00")
+                    .and(predicate::str::contains(
+                        "readable: rosewater=crust flamingo=crust",
+                    ))
+                    .and(predicate::str::contains("lavender=crust"))
+                    .and(predicate::str::contains("candidates: mantle"))
+                    .and(predicate::str::contains("modified: crust")),
+            )
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that `readable_on` picks its default candidates from the flavor being rendered
+    #[test]
+    fn test_readable_on_derived_flavors() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .write_stdin(r#"{{ red | readable_on | get(key="hex") }}"#)
+            .args([
+                "-",
+                "-f",
+                "mocha-oled",
+                "--color-overrides",
+                "tests/fixtures/derived/derived.yml",
+            ])
+            .assert();
+        assert
+            .success()
+            .stdout("000000")
+            .stderr(predicate::str::is_empty());
+
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args([
+                "--dry-run",
+                "tests/fixtures/derived/readable.tera",
+                "--color-overrides",
+                "tests/fixtures/derived/derived.yml",
+            ])
+            .assert();
+        assert
+            .success()
+            .stdout(
+                predicate::str::contains("mocha-11111b.txt")
+                    .and(predicate::str::contains("mocha-oled-000000.txt"))
+                    .and(predicate::str::contains("latte-muted-f2f2f2.txt")),
+            )
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that APCA contrast is computed and checked against font sizes
+    #[test]
+    fn test_apca() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args(["tests/fixtures/apca.tera", "-f", "mocha"])
+            .assert();
+        assert
+            .success()
+            .stdout(
+                predicate::str::contains("lc: -80.0 79.7 0")
+                    .and(predicate::str::contains("readable: false true true false")),
+            )
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that color vision deficiencies can be simulated
+    #[test]
+    fn test_simulate() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args(["tests/fixtures/simulate.tera", "-f", "mocha"])
+            .assert();
+        assert
+            .success()
+            .stdout(
+                predicate::str::contains("protanopia: 9b9fa9")
+                    .and(predicate::str::contains("deuteranopia: b5b2a6"))
+                    .and(predicate::str::contains("tritanopia: ff8395"))
+                    .and(predicate::str::contains("achromatopsia: aaaaaa"))
+                    .and(predicate::str::contains("translucent: aaaaaa80")),
+            )
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that accents which are hard to tell apart are reported
+    #[test]
+    fn test_audit_accents() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args([
+                "--audit-accents",
+                "-f",
+                "mocha",
+                "-o",
+                "plain",
+                "--min-delta-e",
+                "0.5",
+            ])
+            .assert();
+        assert
+            .success()
+            .stdout(predicate::eq(
+                "mocha achromatopsia rosewater yellow 0.00\n\
+                 mocha achromatopsia flamingo green 0.47\n\
+                 mocha achromatopsia flamingo teal 0.47\n\
+                 mocha achromatopsia pink sky 0.48\n\
+                 mocha achromatopsia maroon blue 0.27\n\
+                 mocha achromatopsia peach lavender 0.25\n\
+                 mocha achromatopsia green teal 0.00\n",
+            ))
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that gradients and ramps can be generated
+    #[test]
+    fn test_gradient() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args(["tests/fixtures/gradient.tera", "-f", "mocha"])
+            .assert();
+        assert
+            .success()
+            .stdout(
+                predicate::str::contains("oklab: f38ba8 dd98bd c5a3d2 aaace6 89b4fa")
+                    .and(predicate::str::contains(
+                        "srgb: f38ba8 d895bd bea0d1 a4aae5 89b4fa",
+                    ))
+                    .and(predicate::str::contains(
+                        "oklch: f38ba8 e391cd ca9be9 aba7f9 89b4fa",
+                    ))
+                    .and(predicate::str::contains(
+                        "ramp: ffe8ed fbbaca f38ba8 7a4352 14070a",
+                    ))
+                    .and(predicate::str::contains("translucent: f38ba880 00000080")),
+            )
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that colors can be made from values in the template
+    #[test]
+    fn test_color() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args(["tests/fixtures/color.tera", "-f", "mocha"])
+            .assert();
+        assert
+            .success()
+            .stdout(
+                predicate::str::contains("hex: #ff8800 #ff880080")
+                    .and(predicate::str::contains("rgb: #ff8800 #f38ba8"))
+                    .and(predicate::str::contains("hsl: #f38ba8"))
+                    .and(predicate::str::contains(
+                        "to_color: red #f38ba8 #ff8800 #0a141e80 #010203",
+                    ))
+                    .and(predicate::str::contains("filters: rgb(143, 83, 23)")),
+            )
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that translucent colors can be flattened onto a background
+    #[test]
+    fn test_flatten() {
         let mut cmd = cargo_bin_cmd!("whiskers");
         let assert = cmd
-            .args(["tests/fixtures/formats.tera", "-f", "latte"])
+            .args(["tests/fixtures/flatten.tera", "-f", "mocha"])
             .assert();
         assert
             .success()
             .stdout(
-                predicate::str::contains("24-bit red: 13766457")
-                    .and(predicate::str::contains("unsigned 32-bit red: 4291956537"))
-                    .and(predicate::str::contains("signed 32-bit red: -3010759"))
-                    .and(predicate::str::contains("channels: 210 15 57")),
+                predicate::str::contains("normal: 414356")
+                    .and(predicate::str::contains("opaque: f38ba8"))
+                    .and(predicate::str::contains("transparent: 1e1e2e"))
+                    .and(predicate::str::contains("translucent: d099c3c0"))
+                    .and(predicate::str::contains("multiply: 8362a5"))
+                    .and(predicate::str::contains("screen: f9ddfd"))
+                    .and(predicate::str::contains("overlay: f4bbfc")),
+            )
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that colors map to terminal color indices and escape sequences
+    #[test]
+    fn test_ansi() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args(["tests/fixtures/ansi.tera", "-f", "mocha"])
+            .assert();
+        assert
+            .success()
+            .stdout(
+                predicate::str::contains(
+                    "indices: rosewater=224/7 flamingo=224/7 pink=225/7 mauve=183/13 red=211/13",
+                )
+                .and(predicate::str::contains(
+                    "green=114/2 teal=116/6 sky=116/6 sapphire=117/6",
+                ))
+                .and(predicate::str::contains(
+                    "base=234/0 mantle=234/0 crust=233/0",
+                ))
+                .and(predicate::str::contains(
+                    "truecolor: \x1b[38;2;243;139;168m\x1b[48;2;30;30;46m",
+                ))
+                .and(predicate::str::contains(
+                    "256: \x1b[38;5;211m\x1b[48;5;234m",
+                ))
+                .and(predicate::str::contains("16: \x1b[95m\x1b[47m"))
+                .and(predicate::str::contains("numeric: \x1b[38;5;211m\x1b[95m")),
+            )
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that flavors map the ANSI colors to palette colors
+    #[test]
+    fn test_ansi_colors() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args(["tests/fixtures/terminal.tera", "-f", "mocha"])
+            .assert();
+        assert
+            .success()
+            .stdout(
+                predicate::str::contains("color0=45475a\ncolor1=f38ba8\n")
+                    .and(predicate::str::contains("color7=a6adc8\ncolor8=585b70\n"))
+                    .and(predicate::str::contains("color9=f37799\n"))
+                    .and(predicate::str::contains("color15=bac2de\n"))
+                    .and(predicate::str::contains(
+                        "bright black: Bright Black bright_black",
+                    )),
+            )
+            .stderr(predicate::str::is_empty());
+    }
+
+    /// Test that the ANSI colors follow color overrides
+    #[test]
+    fn test_ansi_colors_overrides() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args([
+                "tests/fixtures/terminal.tera",
+                "-f",
+                "mocha",
+                "--color-overrides",
+                r#"{"mocha": {"red": "ff0000", "surface1": "000000"}}"#,
+            ])
+            .assert();
+        assert
+            .success()
+            .stdout(
+                predicate::str::contains("color0=000000\ncolor1=ff0000\n")
+                    .and(predicate::str::contains("color9=e81f00\n"))
+                    .and(predicate::str::contains("color10=89d88b\n")),
             )
             .stderr(predicate::str::is_empty());
     }
@@ -140,12 +1051,25 @@ mod happy_path {
             .stdout(include_str!("fixtures/hexformat/custom.txt"))
             .stderr(predicate::str::is_empty());
     }
+
+    /// Test that the hex format can be overridden per call of the `hex` filter
+    #[test]
+    fn test_per_call_hex_format() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        let assert = cmd
+            .args(["tests/fixtures/hexformat/per-call.tera", "-f", "latte"])
+            .assert();
+        assert
+            .success()
+            .stdout(include_str!("fixtures/hexformat/per-call.txt"))
+            .stderr(predicate::str::is_empty());
+    }
 }
 
 #[cfg(test)]
 mod sad_path {
     use assert_cmd::cargo::cargo_bin_cmd;
-    use predicates::prelude::predicate;
+    use predicates::prelude::{predicate, PredicateBooleanExt};
 
     #[test]
     fn nonexistent_template_file() {
@@ -166,6 +1090,421 @@ mod sad_path {
         ));
     }
 
+    #[test]
+    fn procreate_too_many_swatches() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.args([
+            "--export",
+            "procreate",
+            "-f",
+            "mocha",
+            "--color-overrides",
+            r#"{"extra": {"a": "fff", "b": "fff", "c": "fff", "d": "fff", "e": "fff"}}"#,
+        ]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "Procreate palettes can hold at most 30 colors, but flavor \"mocha\" has 31",
+        ));
+    }
+
+    #[test]
+    fn procreate_multiple_flavors() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.args(["--export", "procreate"]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "Procreate swatches hold a single palette, so a flavor must be selected with --flavor",
+        ));
+    }
+
+    #[test]
+    fn extends_cycle() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.arg("tests/fixtures/extends/cycle/cycle.tera")
+            .args(["-f", "mocha"]);
+        cmd.assert().failure().stderr(
+            predicate::str::contains("Frontmatter `extends` could not be resolved")
+                .and(predicate::str::contains("a.yml is already being extended")),
+        );
+    }
+
+    #[test]
+    fn extends_missing_file() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.write_stdin("---\nwhiskers:\n  extends: does/not/exist.yml\n---\n")
+            .args(["-", "-f", "mocha"]);
+        cmd.assert().failure().stderr(
+            predicate::str::contains("Failed to read extended file: ")
+                .and(predicate::str::contains("does/not/exist.yml")),
+        );
+    }
+
+    #[test]
+    fn computed_value_fails() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.write_stdin("---\nwhiskers:\n  computed:\n    x: red | nope\n---\n{{x}}")
+            .args(["-", "-f", "mocha"]);
+        cmd.assert().failure().stderr(
+            predicate::str::contains("Frontmatter computed values could not be evaluated")
+                .and(predicate::str::contains("Computed value `x` failed")),
+        );
+    }
+
+    #[test]
+    fn invalid_set_argument() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.arg("tests/fixtures/single/single.tera")
+            .args(["--set", "no-equals-sign"]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "Invalid --set argument \"no-equals-sign\": expected PATH=VALUE",
+        ));
+    }
+
+    #[test]
+    fn invalid_env_overrides() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.arg("tests/fixtures/single/single.tera")
+            .env("WHISKERS_OVERRIDES", "{not json");
+        cmd.assert().failure().stderr(
+            predicate::str::contains("Environment overrides are invalid")
+                .and(predicate::str::contains("WHISKERS_OVERRIDES")),
+        );
+    }
+
+    #[test]
+    fn invalid_color_override() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.arg("tests/fixtures/single/single.tera").args([
+            "-f",
+            "mocha",
+            "--color-overrides",
+            r#"{"all": {"red": "nope"}}"#,
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("Failed to parse color \"nope\""));
+    }
+
+    #[test]
+    fn unknown_color_override() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.arg("tests/fixtures/single/single.tera").args([
+            "-f",
+            "mocha",
+            "--color-overrides",
+            r#"{"all": {"maurve": "fff"}}"#,
+        ]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "Unknown color \"maurve\" in \"all\" color overrides, did you mean \"mauve\"?",
+        ));
+    }
+
+    #[test]
+    fn extra_color_shadows_builtin() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.arg("tests/fixtures/single/single.tera").args([
+            "-f",
+            "mocha",
+            "--color-overrides",
+            r#"{"extra": {"mauve": "fff"}}"#,
+        ]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "Extra color \"mauve\" already exists",
+        ));
+    }
+
+    #[test]
+    fn extra_color_invalid_identifier() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.arg("tests/fixtures/single/single.tera").args([
+            "-f",
+            "mocha",
+            "--color-overrides",
+            r#"{"extra": {"my-color": "fff"}}"#,
+        ]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "Extra color \"my-color\" is not a valid identifier",
+        ));
+    }
+
+    #[test]
+    fn extra_color_reserved() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.arg("tests/fixtures/single/single.tera").args([
+            "-f",
+            "mocha",
+            "--color-overrides",
+            r#"{"extra": {"flavors": "fff"}}"#,
+        ]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "Extra color \"flavors\" is reserved by the template context",
+        ));
+    }
+
+    #[test]
+    fn extra_color_shadows_frontmatter() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.write_stdin("---\nbrand: pink\n---\n{{ brand }}").args([
+            "-",
+            "-f",
+            "mocha",
+            "--color-overrides",
+            r#"{"extra": {"brand": "fff"}}"#,
+        ]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "Extra color \"brand\" clashes with the frontmatter value of the same name",
+        ));
+    }
+
+    #[test]
+    fn extra_color_shadows_computed() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.write_stdin("---\nwhiskers:\n  computed:\n    brand: \"1 + 1\"\n---\n{{ brand }}")
+            .args([
+                "-",
+                "-f",
+                "mocha",
+                "--color-overrides",
+                r#"{"extra": {"brand": "fff"}}"#,
+            ]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "Extra color \"brand\" clashes with the computed value of the same name",
+        ));
+    }
+
+    #[test]
+    fn custom_palette_unknown_field() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.arg("tests/fixtures/single/single.tera").args([
+            "-f",
+            "mocha",
+            "--palette",
+            "tests/fixtures/palette/misspelled.yml",
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("unknown field `emjoi`"));
+    }
+
+    #[test]
+    fn custom_palette_unknown_color_field() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.arg("tests/fixtures/single/single.tera").args([
+            "-f",
+            "mocha",
+            "--palette",
+            r#"{"flavors": {"latte": {"name": "Latte", "emoji": "🌻", "dark": false, "colors": {"brand": {"hex": "fff", "acent": true}}}}}"#,
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("CustomColor"));
+    }
+
+    #[test]
+    fn incomplete_custom_palette() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.arg("tests/fixtures/single/single.tera").args([
+            "-f",
+            "mocha",
+            "--palette",
+            r#"{"flavors": {"latte": {"name": "Latte", "emoji": "🌻", "dark": false, "colors": {}}}}"#,
+        ]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "Custom palette flavor \"latte\" is missing color \"rosewater\"",
+        ));
+    }
+
+    #[test]
+    fn derived_flavor_unknown_parent() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.arg("tests/fixtures/single/single.tera").args([
+            "-f",
+            "oled",
+            "--color-overrides",
+            r#"{"derived": {"oled": {"parent": "mocca"}}}"#,
+        ]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "Derived flavor \"oled\" has unknown parent flavor \"mocca\"",
+        ));
+    }
+
+    #[test]
+    fn derived_flavor_unknown_color() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.arg("tests/fixtures/single/single.tera").args([
+            "-f",
+            "oled",
+            "--color-overrides",
+            r#"{"derived": {"oled": {"parent": "mocha", "operations": [{"set": {"bsae": "000"}}]}}}"#,
+        ]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "Unknown color \"bsae\" in \"derived.oled\" color overrides, did you mean \"base\"?",
+        ));
+    }
+
+    #[test]
+    fn readable_on_no_readable_candidate() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.write_stdin("{{ rosewater | readable_on }}")
+            .args(["-", "-f", "latte"]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "No candidate is readable on rosewater: the best contrast ratio is 3.02, but at least 4.5 is required",
+        ));
+    }
+
+    #[test]
+    fn readable_on_without_flavor_or_candidates() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.write_stdin("{{ flavors.mocha.colors.red | readable_on }}")
+            .arg("-");
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "no flavor is being rendered, so `candidates` must be given",
+        ));
+    }
+
+    #[test]
+    fn ramp_without_tints_or_shades() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.write_stdin("{{ red | ramp }}")
+            .args(["-", "-f", "mocha"]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "at least one of `tints` and `shades` is required",
+        ));
+    }
+
+    #[test]
+    fn ansi_unknown_layer() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.write_stdin(r#"{{ red | ansi(layer="background") }}"#)
+            .args(["-", "-f", "mocha"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("layer must be one of fg or bg"));
+    }
+
+    #[test]
+    fn ansi_non_string_layer() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.write_stdin("{{ red | ansi(layer=1) }}")
+            .args(["-", "-f", "mocha"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("layer must be one of fg or bg"));
+    }
+
+    #[test]
+    fn ansi_non_string_mode() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.write_stdin("{{ red | ansi(mode=true) }}")
+            .args(["-", "-f", "mocha"]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "mode must be one of truecolor, 256 or 16",
+        ));
+    }
+
+    #[test]
+    fn simulate_unknown_deficiency() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.write_stdin(r#"{{ red | simulate(deficiency="protanomaly") }}"#)
+            .args(["-", "-f", "mocha"]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "deficiency must be one of protanopia, deuteranopia, tritanopia or achromatopsia",
+        ));
+    }
+
+    #[test]
+    fn gradient_too_few_steps() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.write_stdin("{{ gradient(from=red, to=blue, steps=1) }}")
+            .args(["-", "-f", "mocha"]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "a gradient needs at least 2 steps",
+        ));
+    }
+
+    #[test]
+    fn unknown_hex_format_variable() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.write_stdin("---\nwhiskers:\n  hex_format: \"#{{rr}}\"\n---\n{{ red.hex }}")
+            .args(["-", "-f", "mocha"]);
+        cmd.assert().failure().stderr(
+            predicate::str::contains("Frontmatter `hex_format` is invalid").and(
+                predicate::str::contains(
+                    "unknown variable `rr`, expected one of r, g, b, a, z, R, G, B, A or Z",
+                ),
+            ),
+        );
+    }
+
+    #[test]
+    fn missing_function_argument() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.write_stdin("{{ contrast(a=red) }}")
+            .args(["-", "-f", "mocha"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("argument `b` is required"));
+    }
+
+    #[test]
+    fn color_without_arguments() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.write_stdin("{{ color() }}").args(["-", "-f", "mocha"]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "exactly one of hex, rgb or hsl is required",
+        ));
+    }
+
+    #[test]
+    fn to_color_invalid_value() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.write_stdin("{{ [1, 2] | to_color }}")
+            .args(["-", "-f", "mocha"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("not a color: expected a color"));
+    }
+
+    #[test]
+    fn to_color_non_color_object() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.write_stdin("{{ flavor | to_color }}")
+            .args(["-", "-f", "mocha"]);
+        cmd.assert().failure().stderr(
+            predicate::str::contains("not a color: expected a color")
+                .and(predicate::str::contains("Rosewater").not())
+                .and(predicate::str::contains("f38ba8").not()),
+        );
+    }
+
+    #[test]
+    fn color_hsl_out_of_range() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.write_stdin("{{ color(hsl=object(h=10, s=50, l=50)) }}")
+            .args(["-", "-f", "mocha"]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "hsl saturation and lightness must be between 0 and 1, got s=50 and l=50",
+        ));
+    }
+
+    #[test]
+    fn flatten_unknown_mode() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.write_stdin(r#"{{ red | flatten(on=base, mode="darken") }}"#)
+            .args(["-", "-f", "mocha"]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "mode must be one of normal, multiply, screen or overlay",
+        ));
+    }
+
+    #[test]
+    fn ansi_unknown_mode() {
+        let mut cmd = cargo_bin_cmd!("whiskers");
+        cmd.write_stdin(r#"{{ red | ansi(mode="88") }}"#)
+            .args(["-", "-f", "mocha"]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "mode must be one of truecolor, 256 or 16",
+        ));
+    }
+
     #[test]
     fn template_contains_invalid_syntax() {
         let mut cmd = cargo_bin_cmd!("whiskers");